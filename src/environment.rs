@@ -1,51 +1,274 @@
-use std::{collections::HashMap, hash::Hash};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::rc::Rc;
 use crate::evaluate::Value;
+use crate::expressions::Resolution;
+use crate::runtime::Runtime;
 
+#[derive(Clone)]
+struct Binding {
+    value: Value,
+    mutable: bool,
+    /// Where the scope holding the binding was cut out of the stored
+    /// function's closure; see `Binding::new`.
+    own_scope: Option<usize>,
+}
+
+type Scope = Rc<RefCell<HashMap<String, Binding>>>;
+
+impl Binding {
+    /// A function stored in a scope its closure contains would keep that
+    /// scope alive through itself, so neither would ever be freed. It is
+    /// stored with the scope cut out of its closure instead, and the scope is
+    /// put back whenever the function is read. Functions inside lists and maps
+    /// are stored as they are.
+    fn new(value: Value, mutable: bool, scope: &Scope) -> Self {
+        if let Value::Function(function) = &value
+            && let Some(index) = function.closure().scope_index(scope)
+        {
+            let closure = function.closure().with_scope(index, Rc::new(RefCell::new(HashMap::new())));
+            let value = Value::function(function.with_closure(closure));
+            return Binding { value, mutable, own_scope: Some(index) };
+        }
+        Binding { value, mutable, own_scope: None }
+    }
+
+    /// The stored value, read from `scope`, the scope holding the binding.
+    fn value(&self, scope: &Scope) -> Value {
+        match (&self.value, self.own_scope) {
+            (Value::Function(function), Some(index)) => {
+                Value::function(function.with_closure(function.closure().with_scope(index, Rc::clone(scope))))
+            },
+            (value, _) => value.clone(),
+        }
+    }
+}
+
+/// A chain of scopes, innermost last. Scopes are shared rather than copied,
+/// so a child environment (a block, a call, or a closure) sees and mutates
+/// the same bindings as the environment it was created from.
+#[derive(Clone)]
 pub struct Environment {
-    stack: Vec<HashMap<String, Value>>,
+    stack: Vec<Scope>,
+    runtime: Rc<RefCell<Runtime>>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Environment {
     pub fn new() -> Self {
         Environment {
-            stack: vec![HashMap::new()],
+            stack: vec![Rc::new(RefCell::new(HashMap::new()))],
+            runtime: Rc::new(RefCell::new(Runtime::new())),
         }
     }
 
     pub fn new_child(env: &Environment) -> Self {
         let mut new_stack = env.stack.clone();
-        new_stack.push(HashMap::new());
+        new_stack.push(Rc::new(RefCell::new(HashMap::new())));
         Environment { 
             stack: new_stack,
+            runtime: Rc::clone(&env.runtime),
         }
     }
 
+    /// An environment holding only the global scope of this one.
+    pub fn global(&self) -> Self {
+        Environment {
+            stack: vec![Rc::clone(&self.stack[0])],
+            runtime: Rc::clone(&self.runtime),
+        }
+    }
+
+    pub fn runtime(&self) -> Ref<'_, Runtime> {
+        self.runtime.borrow()
+    }
+
+    pub fn runtime_mut(&self) -> RefMut<'_, Runtime> {
+        self.runtime.borrow_mut()
+    }
+
     pub fn pop(&mut self) {
         self.stack.pop();
     }
 
+    /// Defines an immutable binding in the innermost scope.
     pub fn define(&mut self, name: String, value: Value) {
-        if let Some(current_scope) = self.stack.last_mut() {
-            current_scope.insert(name, value);
+        self.insert(name, value, false);
+    }
+
+    /// Defines a binding that may later be reassigned (`let mut`).
+    pub fn define_mutable(&mut self, name: String, value: Value) {
+        self.insert(name, value, true);
+    }
+
+    fn insert(&mut self, name: String, value: Value, mutable: bool) {
+        if let Some(current_scope) = self.stack.last() {
+            let binding = Binding::new(value, mutable, current_scope);
+            current_scope.borrow_mut().insert(name, binding);
         }
     }
 
     pub fn get(&self, name: &str) -> Result<Value, String> {
         for scope in self.stack.iter().rev() {
-            if let Some(value) = scope.get(name) {
-                return Ok(value.clone());
+            if let Some(binding) = scope.borrow().get(name) {
+                return Ok(binding.value(scope));
             }
         }
         Err(format!("Undefined variable '{}'.", name))
     }
 
+    /// Looks a variable up in the one scope the resolver chose for it.
+    pub fn get_resolved(&self, name: &str, resolution: Resolution) -> Result<Value, String> {
+        match self.resolved_scope(resolution) {
+            Some(scope) => scope.borrow().get(name)
+                .map(|binding| binding.value(scope))
+                .ok_or_else(|| format!("Undefined variable '{}'.", name)),
+            None => self.get(name),
+        }
+    }
+
+    // Where `scope` is in the chain, if it is in it.
+    fn scope_index(&self, scope: &Scope) -> Option<usize> {
+        self.stack.iter().position(|candidate| Rc::ptr_eq(candidate, scope))
+    }
+
+    // A copy of the chain with the scope at `index` swapped for `scope`.
+    fn with_scope(&self, index: usize, scope: Scope) -> Self {
+        let mut stack = self.stack.clone();
+        stack[index] = scope;
+        Environment { stack, runtime: Rc::clone(&self.runtime) }
+    }
+
+    // `None` when every scope must be searched instead.
+    fn resolved_scope(&self, resolution: Resolution) -> Option<&Scope> {
+        match resolution {
+            Resolution::Unresolved => None,
+            Resolution::Local(depth) => self.stack.len().checked_sub(depth + 1).map(|index| &self.stack[index]),
+            Resolution::Global => self.stack.first(),
+        }
+    }
+
+    /// Lists the user-visible bindings of every scope, innermost first and
+    /// sorted by name, one `name = value` per line. Natives are omitted.
+    pub fn dump_env(&self) -> String {
+        let mut lines = vec![];
+        for (depth, scope) in self.stack.iter().enumerate().rev() {
+            let scope = scope.borrow();
+            let mut names: Vec<&String> = scope.iter()
+                .filter(|(_, binding)| !matches!(binding.value, Value::NativeFn(_)))
+                .map(|(name, _)| name)
+                .collect();
+            names.sort();
+            for name in names {
+                let binding = &scope[name];
+                let keyword = if binding.mutable { "let mut" } else { "let" };
+                lines.push(format!("[{}] {} {} = {}", depth, keyword, name, binding.value.stringify()));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Bindings introduced below the global scope, sorted by name. These are
+    /// what a closure created in this environment has captured.
+    pub fn local_bindings(&self) -> Vec<(String, Value)> {
+        let mut bindings: Vec<(String, Value)> = vec![];
+        for scope in self.stack.iter().skip(1) {
+            for (name, binding) in scope.borrow().iter() {
+                bindings.retain(|(existing, _)| existing != name);
+                bindings.push((name.clone(), binding.value(scope)));
+            }
+        }
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
     pub fn assign(&mut self, name: String, value: Value) -> Result<(), String> {
-        for scope in self.stack.iter_mut().rev() {
-            if scope.contains_key(&name) {
-                scope.insert(name, value);
+        for scope in self.stack.iter().rev() {
+            if let Some(binding) = scope.borrow_mut().get_mut(&name) {
+                if !binding.mutable {
+                    return Err(format!("Cannot assign twice to immutable variable '{}'.", name));
+                }
+                *binding = Binding::new(value, true, scope);
                 return Ok(());
             }
         }
         Err(format!("Undefined variable '{}'.", name))
     }
-}
\ No newline at end of file
+
+    /// Assigns to a variable in the one scope the resolver chose for it.
+    pub fn assign_resolved(&mut self, name: String, value: Value, resolution: Resolution) -> Result<(), String> {
+        let Some(scope) = self.resolved_scope(resolution) else {
+            return self.assign(name, value);
+        };
+        match scope.borrow_mut().get_mut(&name) {
+            Some(binding) if !binding.mutable => Err(format!("Cannot assign twice to immutable variable '{}'.", name)),
+            Some(binding) => {
+                *binding = Binding::new(value, true, scope);
+                Ok(())
+            },
+            None => Err(format!("Undefined variable '{}'.", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assign_mutable() {
+        let mut env = Environment::new();
+        env.define_mutable("x".to_string(), Value::Number(1.0));
+        assert!(env.assign("x".to_string(), Value::Number(2.0)).is_ok());
+        assert_eq!(env.get("x"), Ok(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn test_assign_immutable() {
+        let mut env = Environment::new();
+        env.define("x".to_string(), Value::Number(1.0));
+        let result = env.assign("x".to_string(), Value::Number(2.0));
+        assert_eq!(result, Err("Cannot assign twice to immutable variable 'x'.".to_string()));
+        assert_eq!(env.get("x"), Ok(Value::Number(1.0)));
+    }
+
+    #[test]
+    fn test_assign_undefined() {
+        let mut env = Environment::new();
+        assert!(env.assign("x".to_string(), Value::Nil).is_err());
+    }
+
+    #[test]
+    fn test_child_assign_reaches_parent() {
+        let mut env = Environment::new();
+        env.define_mutable("x".to_string(), Value::Number(1.0));
+        let mut child = Environment::new_child(&env);
+        child.assign("x".to_string(), Value::Number(2.0)).unwrap();
+        assert_eq!(env.get("x"), Ok(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn test_child_define_shadows_locally() {
+        let mut env = Environment::new();
+        env.define_mutable("x".to_string(), Value::Number(1.0));
+        let mut child = Environment::new_child(&env);
+        child.define("x".to_string(), Value::Number(2.0));
+        assert_eq!(child.get("x"), Ok(Value::Number(2.0)));
+        assert_eq!(env.get("x"), Ok(Value::Number(1.0)));
+    }
+
+    #[test]
+    fn test_dump_env() {
+        let mut env = Environment::new();
+        env.define("b".to_string(), Value::Integer(2));
+        env.define_mutable("a".to_string(), Value::string("x"));
+        let mut child = Environment::new_child(&env);
+        child.define("c".to_string(), Value::Bool(true));
+        assert_eq!(child.dump_env(), "[1] let c = true\n[0] let mut a = x\n[0] let b = 2");
+    }
+}
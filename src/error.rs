@@ -1,7 +1,19 @@
+use std::fmt;
+
 #[derive(Debug)]
 pub enum SpadeError {
+    ScanError { message: String, line: usize, column: usize },
+    ParseError { message: String, line: usize, column: usize },
     RuntimeError { message: String, line: usize },
     Return(crate::evaluate::Value),
+    Break,
+    Continue,
+}
+
+/// A problem found while checking source code without running it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
 }
 
 impl SpadeError {
@@ -9,7 +21,59 @@ impl SpadeError {
         SpadeError::RuntimeError { message, line }
     }
     
+    /// Fills in the line of a runtime error raised without one.
+    pub fn at_line(self, line: usize) -> Self {
+        match self {
+            SpadeError::RuntimeError { message, line: 0 } => SpadeError::RuntimeError { message, line },
+            other => other,
+        }
+    }
+
     pub fn return_value(value: crate::evaluate::Value) -> Self {
         SpadeError::Return(value)
     }
 }
+
+impl fmt::Display for SpadeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpadeError::ScanError { message, line, column } | SpadeError::ParseError { message, line, column } => {
+                write!(f, "{} [line {}, column {}]", message, line, column)
+            },
+            SpadeError::RuntimeError { message, line } => {
+                write!(f, "{} [line {}]", message, line)
+            },
+            // Control-flow signals only reach the host if they escape the
+            // construct that should have caught them.
+            SpadeError::Return(_) => write!(f, "unexpected return"),
+            SpadeError::Break => write!(f, "unexpected break"),
+            SpadeError::Continue => write!(f, "unexpected continue"),
+        }
+    }
+}
+
+impl std::error::Error for SpadeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let error = SpadeError::runtime_error("Undefined variable 'x'.".to_string(), 3);
+        assert_eq!(error.to_string(), "Undefined variable 'x'. [line 3]");
+        let error = SpadeError::ParseError { message: "Expect ';'".to_string(), line: 2, column: 7 };
+        assert_eq!(error.to_string(), "Expect ';' [line 2, column 7]");
+        let error = SpadeError::ScanError { message: "Unterminated string".to_string(), line: 3, column: 9 };
+        assert_eq!(error.to_string(), "Unterminated string [line 3, column 9]");
+        assert_eq!(SpadeError::Return(crate::evaluate::Value::Nil).to_string(), "unexpected return");
+    }
+
+    #[test]
+    fn test_boxed_error() {
+        fn run() -> Result<(), Box<dyn std::error::Error>> {
+            Err(SpadeError::runtime_error("boom".to_string(), 1))?
+        }
+        assert_eq!(run().unwrap_err().to_string(), "boom [line 1]");
+    }
+}
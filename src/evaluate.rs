@@ -1,10 +1,21 @@
-use crate::{environment::Environment, error::SpadeError, expressions::{BinaryOp, Expr, Literal, Statement, UnaryOp}};
-use anyhow::Result;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Instant;
 
-#[derive(Clone, Debug)]
+use indexmap::IndexMap;
+
+use crate::{environment::Environment, error::SpadeError, expressions::{BinaryOp, Expr, Line, Literal, MatchArm, Resolution, Statement, UnaryOp}, token::Token};
+
+#[derive(Clone)]
 pub struct SpadeFn {
+    /// `None` for anonymous functions.
+    name: Option<String>,
     parameters: Vec<String>,
-    body: Box<Statement>,
+    body: Rc<Statement>,
+    closure: Environment,
 }
 
 impl PartialEq for SpadeFn {
@@ -13,25 +24,158 @@ impl PartialEq for SpadeFn {
     }
 }
 
+// The closure is left out: it can contain the function itself.
+impl fmt::Debug for SpadeFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpadeFn")
+            .field("name", &self.name)
+            .field("parameters", &self.parameters)
+            .field("body", &self.body)
+            .finish()
+    }
+}
+
 impl SpadeFn {
-    pub fn new(parameters: Vec<String>, body: Box<Statement>) -> Self {
-        SpadeFn { parameters, body }
+    pub fn new(name: Option<String>, parameters: Vec<String>, body: Rc<Statement>, closure: Environment) -> Self {
+        SpadeFn { name, parameters, body, closure }
+    }
+
+    pub(crate) fn closure(&self) -> &Environment {
+        &self.closure
+    }
+
+    /// The same function closing over `closure` instead.
+    pub(crate) fn with_closure(&self, closure: Environment) -> Self {
+        SpadeFn::new(self.name.clone(), self.parameters.clone(), Rc::clone(&self.body), closure)
     }
+
+    /// Renders the variables captured from enclosing (non-global) scopes,
+    /// e.g. `<captured: x=10>`. Captured functions are left out so that a
+    /// closure never prints itself.
+    pub fn captures(&self) -> String {
+        let captured = self.closure.local_bindings().into_iter()
+            .filter(|(_, value)| !matches!(value, Value::Function(_) | Value::NativeFn(_)))
+            .map(|(name, value)| format!("{}={}", name, value.stringify()))
+            .collect::<Vec<String>>();
+        format!("<captured: {}>", captured.join(", "))
+    }
+}
+
+pub type NativeFunction = fn(&mut Environment, Vec<Value>) -> Result<Value, SpadeError>;
+
+/// A function implemented in Rust and exposed to Spade programs.
+#[derive(Clone)]
+pub struct NativeFn {
+    pub name: String,
+    pub function: NativeFunction,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+impl NativeFn {
+    pub fn new(name: &str, function: NativeFunction) -> Self {
+        NativeFn { name: name.to_string(), function }
+    }
+}
+
+impl fmt::Debug for NativeFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl PartialEq for NativeFn {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+#[derive(Clone, Debug)]
 pub enum Value {
     Nil,
     Bool(bool),
+    /// A whole number, from a literal written without a decimal point.
+    Integer(i64),
+    /// A floating-point number.
     Number(f64),
-    String(String),
-    Function(SpadeFn),
+    /// Shared, so copying a string value doesn't copy its text.
+    String(Rc<str>),
+    /// Shared, so copying a function value doesn't copy its body.
+    Function(Rc<SpadeFn>),
+    NativeFn(NativeFn),
+    List(Rc<RefCell<Vec<Value>>>),
+    /// String-keyed map that iterates (and prints) in insertion order.
+    Map(Rc<RefCell<IndexMap<String, Value>>>),
     // Later you can add:
     // Function(LoxFunction),
     // Instance(LoxInstance),
     // Class(LoxClass),
 }
 
+// Integers and floats are compared by value, so `1 == 1.0`.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Integer(a), Value::Number(b)) | (Value::Number(b), Value::Integer(a)) => *a as f64 == *b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => a == b,
+            (Value::NativeFn(a), Value::NativeFn(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+// Numbers, strings and bools are ordered among themselves; other values are
+// only comparable when they are equal.
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a.partial_cmp(b),
+            (Value::Integer(a), Value::Number(b)) => (*a as f64).partial_cmp(b),
+            (Value::Number(a), Value::Integer(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+            (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+            (a, b) if a == b => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+/// The user-facing rendering that `print` uses: `nil`, `true`, numbers as
+/// described in `format_number`, strings without quotes, and functions as
+/// `<fn f>`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Number(n) => write!(f, "{}", format_number(*n)),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Function(function) => match &function.name {
+                Some(name) => write!(f, "<fn {}>", name),
+                None => write!(f, "<fn>"),
+            },
+            Value::NativeFn(native) => write!(f, "{:?}", native),
+            Value::List(elements) => {
+                let elements = elements.borrow().iter().map(|e| e.stringify_nested()).collect::<Vec<String>>();
+                write!(f, "[{}]", elements.join(", "))
+            },
+            Value::Map(entries) => {
+                let entries = entries.borrow().iter()
+                    .map(|(key, value)| format!("{:?}: {}", key, value.stringify_nested()))
+                    .collect::<Vec<String>>();
+                write!(f, "{{{}}}", entries.join(", "))
+            },
+        }
+    }
+}
+
 impl Value {
     pub fn is_truthy(&self) -> bool {
         match self {
@@ -40,54 +184,156 @@ impl Value {
             _ => true,
         }
     }
+
+    /// The user-facing name of this value's type, used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "bool",
+            Value::Integer(_) => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Function(_) => "function",
+            Value::NativeFn(_) => "native function",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+
+    pub fn stringify(&self) -> String {
+        self.to_string()
+    }
+
+    /// Like `stringify`, but puts each element of a non-empty list or map on
+    /// its own line, indented two spaces per level of nesting. `indent` is
+    /// the nesting level the value itself starts at.
+    pub fn pretty(&self, indent: usize) -> String {
+        let pad = "  ".repeat(indent + 1);
+        let lines = match self {
+            Value::List(elements) if !elements.borrow().is_empty() => elements.borrow().iter()
+                .map(|element| format!("{}{}", pad, element.pretty_nested(indent + 1)))
+                .collect::<Vec<String>>(),
+            Value::Map(entries) if !entries.borrow().is_empty() => entries.borrow().iter()
+                .map(|(key, value)| format!("{}{:?}: {}", pad, key, value.pretty_nested(indent + 1)))
+                .collect::<Vec<String>>(),
+            _ => return self.stringify(),
+        };
+        let (open, close) = if matches!(self, Value::List(_)) { ("[", "]") } else { ("{", "}") };
+        format!("{}\n{}\n{}{}", open, lines.join(",\n"), "  ".repeat(indent), close)
+    }
+
+    fn pretty_nested(&self, indent: usize) -> String {
+        match self {
+            Value::List(_) | Value::Map(_) => self.pretty(indent),
+            other => other.stringify_nested(),
+        }
+    }
+
+    // Inside collections strings are quoted so `["a b"]` isn't ambiguous.
+    pub(crate) fn stringify_nested(&self) -> String {
+        match self {
+            Value::String(s) => format!("{:?}", s),
+            other => other.stringify(),
+        }
+    }
+
+    /// Copies lists and maps all the way down, so the result shares no
+    /// mutable storage with `self`. A plain `clone` only copies the `Rc`, and
+    /// both values then see each other's mutations. Scalars are copied as
+    /// usual and functions keep sharing their closure environment.
+    pub fn deep_clone(&self) -> Value {
+        match self {
+            Value::List(elements) => Value::list(elements.borrow().iter().map(Value::deep_clone).collect()),
+            Value::Map(entries) => Value::map(
+                entries.borrow().iter().map(|(key, value)| (key.clone(), value.deep_clone())).collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+
+    pub fn string(s: impl Into<Rc<str>>) -> Self {
+        Value::String(s.into())
+    }
+
+    pub fn function(function: SpadeFn) -> Self {
+        Value::Function(Rc::new(function))
+    }
+
+    pub fn list(elements: Vec<Value>) -> Self {
+        Value::List(Rc::new(RefCell::new(elements)))
+    }
+
+    pub fn map(entries: IndexMap<String, Value>) -> Self {
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
 }
 
-pub fn evaluate_statement(stmt: Statement, env: &mut Environment) -> Result<Value, SpadeError> {
+// Significant digits shown for a number. Decimal literals with up to this many
+// digits print back as written, while float noise beyond it is rounded away,
+// so `0.1 + 0.2` prints as `0.3`.
+const SIGNIFICANT_DIGITS: i32 = 15;
+
+/// Formats a float for display. Whole numbers keep one decimal place, so they
+/// don't read as integers, and others are rounded to 15 significant digits
+/// with trailing zeros trimmed: `1000000.0`, `0.0001`, `0.3`. Magnitudes from 1e21 up or below 1e-7 use
+/// exponent form instead (`1e21`, `1.5e-8`), so they don't print as long runs
+/// of zeros. Infinities and NaN print as `inf`, `-inf` and `NaN`.
+fn format_number(n: f64) -> String {
+    let magnitude = n.abs();
+    if !n.is_finite() {
+        n.to_string()
+    } else if n != 0.0 && !(1e-7..1e21).contains(&magnitude) {
+        let formatted = format!("{:.*e}", SIGNIFICANT_DIGITS as usize - 1, n);
+        let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+        format!("{}e{}", trim_fraction(mantissa), exponent)
+    } else if n.fract() == 0.0 {
+        format!("{:.1}", n)
+    } else {
+        let decimals = SIGNIFICANT_DIGITS - 1 - magnitude.log10().floor() as i32;
+        trim_fraction(&format!("{:.*}", decimals.max(0) as usize, n)).to_string()
+    }
+}
+
+// Drops trailing zeros after the decimal point, and the point if nothing is left.
+fn trim_fraction(number: &str) -> &str {
+    if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    }
+}
+
+// Evaluation recurses through this function and `evaluate_expression` at
+// every level of nesting and every call, so both borrow the tree rather than
+// moving nodes around, and keep only their cheapest arms inline. Unoptimized
+// builds give every arm's locals their own stack slots, and a fat frame here
+// would be paid for at every level of the host stack.
+pub fn evaluate_statement(stmt: &Statement, env: &mut Environment) -> Result<Value, SpadeError> {
     match stmt {
-        Statement::Expression(expr) => {
-            evaluate_expression(expr, env)?;
-            Ok(Value::Nil)
-        },
-        Statement::Fn { name, parameters, body } => {
-            env.define(name, Value::Function(SpadeFn::new(parameters, body)));
-            Ok(Value::Nil)
-        },
-        Statement::Print(expr)  => {
-            let val = evaluate_expression(expr, env)?;
-            println!("{:?}", val);
-            Ok(Value::Nil)
-        },
+        Statement::Expression(expr) => evaluate_expression(expr, env),
+        Statement::Fn { name, parameters, body } => define_function(name, parameters, body, env),
+        Statement::Print(exprs) => evaluate_print(exprs, env),
         Statement::Return(expr) => {
-            match expr {
-                Some(expr) => {
-                    let val = evaluate_expression(expr, env)?;
-                    return Err(SpadeError::return_value(val));
-                },
-                None => Ok(Value::Nil),
-            }
-        },
-        Statement::Block(statements) => {
-            let mut env = Environment::new_child(env);
-            for statement in statements {
-                evaluate_statement(statement, &mut env)?;
-            }
-            env.pop();
-            Ok(Value::Nil)
-        },
-        Statement::VarDec { name, initializer } => {
-            let value = match initializer {
+            let value = match expr {
                 Some(expr) => evaluate_expression(expr, env)?,
                 None => Value::Nil,
             };
-            env.define(name, value);
-            Ok(Value::Nil)
+            Err(SpadeError::return_value(value))
         },
+        Statement::Block(statements) => evaluate_block(statements, env),
+        Statement::VarDec { name, initializer, mutable } => evaluate_var_dec(name, initializer.as_ref(), *mutable, env),
+        Statement::While { condition, body, increment } => evaluate_while(condition, body, increment.as_ref(), env),
+        Statement::ForIn { name, iterable, body, line: Line(line) } => evaluate_for_in(name, iterable, body, *line, env),
+        Statement::Import { path, line: Line(line) } => import_file(Path::new(path), *line, env).map(|_| Value::Nil),
+        Statement::Break => Err(SpadeError::Break),
+        Statement::Continue => Err(SpadeError::Continue),
+        Statement::Match { subject, arms } => evaluate_match(subject, arms, env),
+        Statement::With { name, resource, body } => evaluate_with(name, resource, body, env),
         Statement::If { condition, then_branch, else_branch } => {
-            let condition_val = evaluate_expression(condition, env)?;
-            if condition_val.is_truthy() {
-                evaluate_statement(*then_branch, env)
+            if evaluate_expression(condition, env)?.is_truthy() {
+                evaluate_statement(then_branch, env)
             } else if let Some(else_branch) = else_branch {
-                evaluate_statement(*else_branch, env)
+                evaluate_statement(else_branch, env)
             } else {
                 Ok(Value::Nil)
             }
@@ -95,116 +341,672 @@ pub fn evaluate_statement(stmt: Statement, env: &mut Environment) -> Result<Valu
     }
 }
 
-pub fn evaluate_function(fun: SpadeFn, arguments: Vec<Expr>, env: &mut Environment) -> Result<Value, SpadeError> {
+fn define_function(name: &str, parameters: &[String], body: &Rc<Statement>, env: &mut Environment) -> Result<Value, SpadeError> {
+    let function = SpadeFn::new(Some(name.to_string()), parameters.to_vec(), Rc::clone(body), env.clone());
+    env.define(name.to_string(), Value::function(function));
+    Ok(Value::Nil)
+}
+
+fn evaluate_print(exprs: &[Expr], env: &mut Environment) -> Result<Value, SpadeError> {
+    let values = exprs.iter().map(|expr| evaluate_expression(expr, env)).collect::<Result<Vec<Value>, SpadeError>>()?;
+    print_values(&values, env)?;
+    Ok(Value::Nil)
+}
+
+fn evaluate_block(statements: &[Statement], env: &mut Environment) -> Result<Value, SpadeError> {
     let mut env = Environment::new_child(env);
+    for statement in statements {
+        evaluate_statement(statement, &mut env)?;
+    }
+    env.pop();
+    Ok(Value::Nil)
+}
+
+fn evaluate_var_dec(name: &str, initializer: Option<&Expr>, mutable: bool, env: &mut Environment) -> Result<Value, SpadeError> {
+    let value = match initializer {
+        Some(expr) => evaluate_expression(expr, env)?,
+        None => Value::Nil,
+    };
+    if mutable {
+        env.define_mutable(name.to_string(), value);
+    } else {
+        env.define(name.to_string(), value);
+    }
+    Ok(Value::Nil)
+}
+
+// The body runs in the enclosing scope, so assignments made in one
+// iteration are visible to the condition and to the next iteration.
+fn evaluate_while(condition: &Expr, body: &Statement, increment: Option<&Expr>, env: &mut Environment) -> Result<Value, SpadeError> {
+    while evaluate_expression(condition, env)?.is_truthy() {
+        match evaluate_statement(body, env) {
+            Ok(_) | Err(SpadeError::Continue) => {},
+            Err(SpadeError::Break) => break,
+            Err(e) => return Err(e),
+        }
+        if let Some(increment) = increment {
+            evaluate_expression(increment, env)?;
+        }
+    }
+    Ok(Value::Nil)
+}
+
+// Iterates over a snapshot, so the body may modify the list.
+fn evaluate_for_in(name: &str, iterable: &Expr, body: &Statement, line: usize, env: &mut Environment) -> Result<Value, SpadeError> {
+    let elements = match evaluate_expression(iterable, env)? {
+        Value::List(elements) => elements.borrow().clone(),
+        Value::String(s) => s.chars().map(|c| Value::string(c.to_string())).collect(),
+        other => return Err(SpadeError::runtime_error(format!("cannot iterate over {}", other.type_name()), line)),
+    };
+    for element in elements {
+        let mut scope = Environment::new_child(env);
+        scope.define(name.to_string(), element);
+        let result = evaluate_statement(body, &mut scope);
+        scope.pop();
+        match result {
+            Ok(_) | Err(SpadeError::Continue) => {},
+            Err(SpadeError::Break) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Value::Nil)
+}
+
+fn evaluate_match(subject: &Expr, arms: &[MatchArm], env: &mut Environment) -> Result<Value, SpadeError> {
+    let subject = evaluate_expression(subject, env)?;
+    for arm in arms {
+        let matches = match &arm.pattern {
+            Some(pattern) => evaluate_expression(pattern, env)? == subject,
+            None => true,
+        };
+        if !matches {
+            continue;
+        }
+        if let Some(guard) = &arm.guard
+            && !evaluate_expression(guard, env)?.is_truthy()
+        {
+            continue;
+        }
+        evaluate_statement(&arm.body, env)?;
+        break;
+    }
+    Ok(Value::Nil)
+}
+
+fn evaluate_with(name: &str, resource: &Expr, body: &Statement, env: &mut Environment) -> Result<Value, SpadeError> {
+    let resource = evaluate_expression(resource, env)?;
+    let mut scope = Environment::new_child(env);
+    scope.define(name.to_string(), resource.clone());
+    // Like `finally`: the cleanup runs whether the body completed,
+    // returned or failed, and the body's outcome is kept. A cleanup error is
+    // only reported if the body succeeded.
+    let result = evaluate_statement(body, &mut scope);
+    let cleanup = env.runtime_mut().take_cleanup(&resource);
+    let cleaned_up = match cleanup {
+        Some(cleanup) => call_value(cleanup, vec![resource], env).map(|_| ()),
+        None => Ok(()),
+    };
+    result?;
+    cleaned_up.map(|_| Value::Nil)
+}
+
+/// Runs another file's statements in the global scope of `env`, resolving a
+/// relative `path` against the directory of the file currently running. A
+/// file that was already imported, or that is still running further up a
+/// circular import, is skipped. Errors from the file name it and are reported
+/// at the `import`'s line.
+pub fn import_file(path: &Path, line: usize, env: &mut Environment) -> Result<(), SpadeError> {
+    let resolved = match env.runtime().running_files.last() {
+        Some(current) => current.parent().unwrap_or(Path::new("")).join(path),
+        None => path.to_path_buf(),
+    };
+    let cannot_import = |message: String| {
+        SpadeError::runtime_error(format!("Cannot import '{}': {}", path.display(), message), line)
+    };
+    let canonical = std::fs::canonicalize(&resolved).map_err(|e| cannot_import(e.to_string()))?;
+    let skip = {
+        let runtime = env.runtime();
+        runtime.imported.contains(&canonical) || runtime.running_files.contains(&canonical)
+    };
+    if skip {
+        return Ok(());
+    }
+    run_file(&canonical, env).map(|_| ()).map_err(|e| cannot_import(e.to_string()))
+}
+
+/// Runs a file's statements in the global scope of `env`, even if it has been
+/// imported before, and returns the value of its last statement (or of its
+/// top-level `return`). Once it has run successfully, importing it again does
+/// nothing.
+pub fn run_file(path: &Path, env: &mut Environment) -> Result<Value, SpadeError> {
+    let cannot_read = |e: std::io::Error| {
+        SpadeError::runtime_error(format!("Cannot read '{}': {}", path.display(), e), 0)
+    };
+    let canonical = std::fs::canonicalize(path).map_err(cannot_read)?;
+    let source = std::fs::read_to_string(&canonical).map_err(cannot_read)?;
+    let mut statements = crate::spade::parse_source(&source)?;
+    crate::resolver::resolve(&mut statements)?;
+
+    env.runtime_mut().running_files.push(canonical.clone());
+    let mut global = env.global();
+    let mut result = Ok(Value::Nil);
+    for statement in &statements {
+        result = evaluate_statement(statement, &mut global);
+        if result.is_err() {
+            break;
+        }
+    }
+    env.runtime_mut().running_files.pop();
+    let value = match result {
+        Err(SpadeError::Return(value)) => value,
+        other => other?,
+    };
+    env.runtime_mut().imported.insert(canonical);
+    Ok(value)
+}
+
+/// Writes values to the output sink as `print` shows them, separated by
+/// spaces and followed by a newline.
+pub fn print_values(values: &[Value], env: &Environment) -> Result<(), SpadeError> {
+    let text = values.iter().map(|value| print_format(value, env)).collect::<Vec<String>>().join(" ");
+    let output = env.runtime().output.clone();
+    writeln!(output.borrow_mut(), "{}", text)
+        .map_err(|e| SpadeError::runtime_error(format!("Failed to write output: {}", e), 0))
+}
+
+/// Renders a value the way `print` shows it: through the host's formatter if
+/// one is installed, otherwise with `stringify` (plus captured variables for
+/// functions in debug-print mode).
+pub fn print_format(value: &Value, env: &Environment) -> String {
+    let formatter = env.runtime().formatter.clone();
+    if let Some(formatter) = formatter {
+        return formatter(value);
+    }
+    match value {
+        Value::Function(fun) if env.runtime().debug_print => format!("{} {}", value.stringify(), fun.captures()),
+        _ => value.stringify(),
+    }
+}
+
+pub fn evaluate_function(fun: &SpadeFn, arguments: Vec<Value>) -> Result<Value, SpadeError> {
+    let mut env = Environment::new_child(&fun.closure);
     if fun.parameters.len() != arguments.len() {
-        return Err(SpadeError::runtime_error("Expected number of arguments to match number of parameters".to_string(), 0));
+        let callee = match &fun.name {
+            Some(name) => format!("{}()", name),
+            None => "Anonymous function".to_string(),
+        };
+        return Err(SpadeError::runtime_error(
+            format!("{} expects {} argument(s) but got {}", callee, fun.parameters.len(), arguments.len()),
+            0,
+        ));
     }
     // Fill the environment with the arguments
-    for (i, argument) in arguments.iter().enumerate() {
-        let value = evaluate_expression(argument.clone(), &mut env)?;
-        env.define(fun.parameters[i].clone(), value);
+    for (parameter, value) in fun.parameters.iter().zip(arguments) {
+        env.define(parameter.clone(), value);
     }
     // Evaluate the body of the function
-    match evaluate_statement(*fun.body, &mut env) {
+    match evaluate_statement(&fun.body, &mut env) {
         Ok(value) => Ok(value),
         Err(SpadeError::Return(value)) => Ok(value),
         Err(e) => Err(e),
     }
 }
 
-pub fn evaluate_expression(expr: Expr, env: &mut Environment) -> Result<Value, SpadeError> {
-    match expr {
-        Expr::Binary { left, op, right } => {
-            let left_val = evaluate_expression(*left, env)?;
-            let right_val = evaluate_expression(*right, env)?;
-            evaluate_binary(left_val, op, right_val)
-        },
-        Expr::Unary { op, expr } => {
-            let val = evaluate_expression(*expr, env)?;
-            
-            match op {
-                UnaryOp::Minus => {
-                    match val {
-                        Value::Number(n) => Ok(Value::Number(-n)),
-                        _ => Err(SpadeError::runtime_error("Invalid operand for unary -".to_string(), 0)),
-                    }
-                },
-                UnaryOp::Not => {
-                    match val {
-                        Value::Bool(b) => Ok(Value::Bool(!b)),
-                        Value::Nil => Ok(Value::Bool(true)),
-                        _ => Ok(Value::Bool(false)),
-                    }
-                },
+/// Calls a function value, whether user-defined or native.
+pub fn call_value(callee: Value, arguments: Vec<Value>, env: &mut Environment) -> Result<Value, SpadeError> {
+    if !matches!(callee, Value::Function(_) | Value::NativeFn(_)) {
+        return Err(SpadeError::runtime_error("Expected function".to_string(), 0));
+    }
+    let max_call_depth = env.runtime().max_call_depth;
+    if env.runtime().call_depth >= max_call_depth {
+        return Err(SpadeError::runtime_error(
+            format!("stack overflow: more than {} nested calls", max_call_depth),
+            0,
+        ));
+    }
+    // Ordinary calls pay for neither tracing nor profiling, in time or in
+    // stack space.
+    if env.runtime().trace_calls || env.runtime().profiling {
+        return call_instrumented(callee, arguments, env);
+    }
+    env.runtime_mut().call_depth += 1;
+    let result = call_function(callee, arguments, env);
+    env.runtime_mut().call_depth -= 1;
+    result
+}
+
+fn call_function(callee: Value, arguments: Vec<Value>, env: &mut Environment) -> Result<Value, SpadeError> {
+    match callee {
+        Value::Function(fun) => evaluate_function(&fun, arguments),
+        Value::NativeFn(native) => (native.function)(env, arguments),
+        _ => unreachable!(),
+    }
+}
+
+// `call_value` with call tracing or profiling switched on.
+fn call_instrumented(callee: Value, arguments: Vec<Value>, env: &mut Environment) -> Result<Value, SpadeError> {
+    let name = match &callee {
+        Value::Function(fun) => fun.name.clone().unwrap_or_else(|| "<fn>".to_string()),
+        Value::NativeFn(native) => native.name.clone(),
+        _ => unreachable!(),
+    };
+    let tracing = env.runtime().trace_calls;
+    if tracing {
+        let arguments = arguments.iter().map(|a| a.stringify_nested()).collect::<Vec<String>>();
+        env.runtime().trace(&format!("-> {}({})", name, arguments.join(", ")));
+    }
+    env.runtime_mut().call_depth += 1;
+    // Only user-defined functions are profiled, so natives don't pay for
+    // reading the clock.
+    let profiled = if env.runtime().profiling && matches!(callee, Value::Function(_)) {
+        Some((env.runtime_mut().profile_enter(&name), Instant::now()))
+    } else {
+        None
+    };
+    let result = call_function(callee, arguments, env);
+    if let Some((outermost, started)) = profiled {
+        env.runtime_mut().profile_exit(started.elapsed(), outermost);
+    }
+    env.runtime_mut().call_depth -= 1;
+    if tracing {
+        let runtime = env.runtime();
+        match &result {
+            Ok(value) => runtime.trace(&format!("<- {} = {}", name, value.stringify_nested())),
+            Err(_) => runtime.trace(&format!("<- {} failed", name)),
+        }
+    }
+    result
+}
+
+// Evaluates call arguments left to right, flattening `...list` spreads into
+// individual positional arguments.
+fn evaluate_arguments(arguments: &[Expr], env: &mut Environment) -> Result<Vec<Value>, SpadeError> {
+    let mut values = vec![];
+    for argument in arguments {
+        if let Expr::Spread(inner) = argument {
+            match evaluate_expression(inner, env)? {
+                Value::List(elements) => values.extend(elements.borrow().iter().cloned()),
+                other => return Err(SpadeError::runtime_error(format!("Cannot spread a {}, expected a list", other.type_name()), 0)),
             }
-        },
-        Expr::Literal(literal) => {
-            if let Literal::Var(token) = literal {
-                let value = env.get(&token.lexeme).map_err(|e| SpadeError::runtime_error(e.to_string(), token.line))?;
-                Ok(value)
+        } else {
+            values.push(evaluate_expression(argument, env)?);
+        }
+    }
+    Ok(values)
+}
+
+// See `evaluate_statement` for why most arms are helpers.
+pub fn evaluate_expression(expr: &Expr, env: &mut Environment) -> Result<Value, SpadeError> {
+    match expr {
+        Expr::Binary { left, op: op @ (BinaryOp::And | BinaryOp::Or), right, .. } => evaluate_logical(left, *op, right, env),
+        Expr::Binary { left, op, right, line: Line(line) } => evaluate_operator(left, *op, right, *line, env),
+        Expr::Unary { op, expr, line: Line(line) } => evaluate_unary(*op, expr, *line, env),
+        Expr::Literal(literal) => evaluate_literal(literal, env),
+        Expr::Call { callee, arguments, line: Line(line) } => evaluate_call(callee, arguments, *line, env),
+        Expr::Spread(_) => Err(SpadeError::runtime_error("Spread '...' is only allowed in call arguments".to_string(), 0)),
+        Expr::Get { object, name } => evaluate_get(object, name, env),
+        Expr::List(elements) => evaluate_list(elements, env),
+        Expr::Interpolation(parts) => evaluate_interpolation(parts, env),
+        Expr::Map(entries) => evaluate_map(entries, env),
+        Expr::Index { object, index, line: Line(line) } => evaluate_subscript(object, index, *line, env),
+        Expr::Grouping(expr) => evaluate_expression(expr, env),
+        Expr::Conditional { condition, then_branch, else_branch } => {
+            if evaluate_expression(condition, env)?.is_truthy() {
+                evaluate_expression(then_branch, env)
             } else {
-                Ok(literal_to_value(literal))
+                evaluate_expression(else_branch, env)
             }
         },
-        Expr::Call { callee, arguments } => {
-            let callee_val = evaluate_expression(*callee, env)?;
-            match callee_val {
-                Value::Function(fun) => {
-                    evaluate_function(fun, arguments, env)
-                },
-                _ => Err(SpadeError::runtime_error("Expected function".to_string(), 0)),
+        Expr::Let { name, value, body } => evaluate_let(name, value, body, env),
+        Expr::Function { parameters, body } => evaluate_function_literal(parameters, body, env),
+        Expr::Assign { token, value, resolution } => evaluate_assign(token, value, *resolution, env),
+    }
+}
+
+// `and`/`or` short-circuit and yield the deciding operand itself rather than
+// a bool.
+fn evaluate_logical(left: &Expr, op: BinaryOp, right: &Expr, env: &mut Environment) -> Result<Value, SpadeError> {
+    let left_val = evaluate_expression(left, env)?;
+    if left_val.is_truthy() == matches!(op, BinaryOp::Or) {
+        Ok(left_val)
+    } else {
+        evaluate_expression(right, env)
+    }
+}
+
+fn evaluate_operator(left: &Expr, op: BinaryOp, right: &Expr, line: usize, env: &mut Environment) -> Result<Value, SpadeError> {
+    let left_val = evaluate_expression(left, env)?;
+    let right_val = evaluate_expression(right, env)?;
+    // Equality keeps its meaning for nil; everything else yields nil.
+    if env.runtime().nil_propagation
+        && !matches!(op, BinaryOp::EqualEqual | BinaryOp::NotEqual)
+        && (left_val == Value::Nil || right_val == Value::Nil)
+    {
+        return Ok(Value::Nil);
+    }
+    let ieee_division = op == BinaryOp::Divide && !env.runtime().strict_division;
+    let result = match Operands::of(&left_val, &right_val) {
+        Some(operands) if ieee_division => {
+            let (l, r) = operands.floats();
+            Ok(Value::Number(l / r))
+        },
+        _ => evaluate_binary(left_val, op, right_val),
+    }.map_err(|e| e.at_line(line))?;
+    if let Value::Number(n) = result
+        && !n.is_finite()
+        && env.runtime().strict_math
+    {
+        return Err(SpadeError::runtime_error(format!("Operator {} produced a non-finite number ({})", op, n), line));
+    }
+    Ok(result)
+}
+
+fn evaluate_unary(op: UnaryOp, expr: &Expr, line: usize, env: &mut Environment) -> Result<Value, SpadeError> {
+    let val = evaluate_expression(expr, env)?;
+    match op {
+        UnaryOp::Minus => {
+            match val {
+                Value::Integer(n) => n.checked_neg().map(Value::Integer).ok_or_else(|| {
+                    SpadeError::runtime_error("Integer overflow in unary '-'".to_string(), line)
+                }),
+                Value::Number(n) => Ok(Value::Number(-n)),
+                Value::Nil if env.runtime().nil_propagation => Ok(Value::Nil),
+                _ => Err(SpadeError::runtime_error(format!("cannot apply unary '-' to {}", val.type_name()), line)),
             }
-        }
-        Expr::Grouping(expr) => evaluate_expression(*expr, env),
-        // Expr::Variable(token) => 
-        _ => unimplemented!()
+        },
+        // Only `nil` and `false` are falsy, so `!0` and `!""` are false.
+        UnaryOp::Not => Ok(Value::Bool(!val.is_truthy())),
+    }
+}
+
+fn evaluate_literal(literal: &Literal, env: &mut Environment) -> Result<Value, SpadeError> {
+    if let Literal::Var(token, resolution) = literal {
+        let value = env.get_resolved(&token.lexeme, *resolution).map_err(|e| SpadeError::runtime_error(e.to_string(), token.line))?;
+        Ok(value)
+    } else {
+        Ok(literal_to_value(literal))
+    }
+}
+
+fn evaluate_call(callee: &Expr, arguments: &[Expr], line: usize, env: &mut Environment) -> Result<Value, SpadeError> {
+    if let Expr::Get { object, name } = callee {
+        let receiver = evaluate_expression(object, env)?;
+        let values = evaluate_arguments(arguments, env)?;
+        return call_method(receiver, name, values);
+    }
+    let callee_val = evaluate_expression(callee, env)?;
+    let values = evaluate_arguments(arguments, env)?;
+    // Errors raised by the call itself, such as a wrong argument
+    // count, are reported at the call site.
+    call_value(callee_val, values, env).map_err(|e| e.at_line(line))
+}
+
+// Maps are the only values with properties: `m.key` is `m["key"]`.
+fn evaluate_get(object: &Expr, name: &Token, env: &mut Environment) -> Result<Value, SpadeError> {
+    match evaluate_expression(object, env)? {
+        Value::Map(entries) => map_entry(&entries.borrow(), &name.lexeme).map_err(|e| e.at_line(name.line)),
+        other => Err(SpadeError::runtime_error(
+            format!("Cannot read property '{}' of {}", name.lexeme, other.type_name()),
+            name.line,
+        )),
     }
 }
 
-fn literal_to_value(literal: Literal) -> Value {
+fn evaluate_list(elements: &[Expr], env: &mut Environment) -> Result<Value, SpadeError> {
+    let mut values = vec![];
+    for element in elements {
+        values.push(evaluate_expression(element, env)?);
+    }
+    Ok(Value::list(values))
+}
+
+// Each part is rendered the way `str` renders it.
+fn evaluate_interpolation(parts: &[Expr], env: &mut Environment) -> Result<Value, SpadeError> {
+    let mut text = String::new();
+    for part in parts {
+        text.push_str(&evaluate_expression(part, env)?.stringify());
+    }
+    Ok(Value::string(text))
+}
+
+fn evaluate_map(entries: &[(Expr, Expr)], env: &mut Environment) -> Result<Value, SpadeError> {
+    let mut map = IndexMap::new();
+    for (key, value) in entries {
+        let key = match evaluate_expression(key, env)? {
+            Value::String(key) => key.to_string(),
+            other => return Err(SpadeError::runtime_error(format!("Map keys must be strings, got {}", other.type_name()), 0)),
+        };
+        let value = evaluate_expression(value, env)?;
+        map.insert(key, value);
+    }
+    Ok(Value::map(map))
+}
+
+fn evaluate_subscript(object: &Expr, index: &Expr, line: usize, env: &mut Environment) -> Result<Value, SpadeError> {
+    let object_val = evaluate_expression(object, env)?;
+    let index_val = evaluate_expression(index, env)?;
+    evaluate_index(object_val, index_val).map_err(|e| e.at_line(line))
+}
+
+fn evaluate_let(name: &str, value: &Expr, body: &Expr, env: &mut Environment) -> Result<Value, SpadeError> {
+    let value = evaluate_expression(value, env)?;
+    let mut scope = Environment::new_child(env);
+    scope.define(name.to_string(), value);
+    evaluate_expression(body, &mut scope)
+}
+
+fn evaluate_function_literal(parameters: &[String], body: &Rc<Statement>, env: &mut Environment) -> Result<Value, SpadeError> {
+    Ok(Value::function(SpadeFn::new(None, parameters.to_vec(), Rc::clone(body), env.clone())))
+}
+
+fn evaluate_assign(token: &Token, value: &Expr, resolution: Resolution, env: &mut Environment) -> Result<Value, SpadeError> {
+    let value = evaluate_expression(value, env)?;
+    env.assign_resolved(token.lexeme.clone(), value.clone(), resolution).map_err(|e| SpadeError::runtime_error(e, token.line))?;
+    Ok(value)
+}
+
+// Built-in methods on primitive values, dispatched on the receiver's variant.
+fn call_method(receiver: Value, name: &Token, arguments: Vec<Value>) -> Result<Value, SpadeError> {
+    let method = name.lexeme.as_str();
+    let unknown = |receiver: &Value| {
+        SpadeError::runtime_error(format!("{} has no method '{}'", receiver.type_name(), method), name.line)
+    };
+    if !arguments.is_empty() {
+        return Err(SpadeError::runtime_error(
+            format!("Method '{}' expects 0 arguments but got {}", method, arguments.len()),
+            name.line,
+        ));
+    }
+    match &receiver {
+        Value::Integer(n) => match method {
+            "floor" | "ceil" | "round" => Ok(Value::Integer(*n)),
+            "abs" => n.checked_abs().map(Value::Integer).ok_or_else(|| {
+                SpadeError::runtime_error("Integer overflow in abs".to_string(), name.line)
+            }),
+            "to_string" => Ok(Value::string(receiver.stringify())),
+            _ => Err(unknown(&receiver)),
+        },
+        Value::Number(n) => match method {
+            "floor" => Ok(Value::Number(n.floor())),
+            "ceil" => Ok(Value::Number(n.ceil())),
+            "round" => Ok(Value::Number(n.round())),
+            "abs" => Ok(Value::Number(n.abs())),
+            "to_string" => Ok(Value::string(receiver.stringify())),
+            _ => Err(unknown(&receiver)),
+        },
+        Value::Bool(b) => match method {
+            "not" => Ok(Value::Bool(!b)),
+            "to_string" => Ok(Value::string(receiver.stringify())),
+            _ => Err(unknown(&receiver)),
+        },
+        _ => Err(unknown(&receiver)),
+    }
+}
+
+// Reading a key that isn't in the map is an error rather than nil, so typos
+// in key names don't go unnoticed.
+fn map_entry(entries: &IndexMap<String, Value>, key: &str) -> Result<Value, SpadeError> {
+    entries.get(key).cloned().ok_or_else(|| {
+        SpadeError::runtime_error(format!("Map has no key '{}' (reading a missing key is an error)", key), 0)
+    })
+}
+
+// Lists are indexed by element and strings by Unicode scalar value (char),
+// never by byte, so `"héllo"[1]` is `"é"`.
+fn evaluate_index(object: Value, index: Value) -> Result<Value, SpadeError> {
+    if let Value::Map(entries) = &object {
+        return match index {
+            Value::String(key) => map_entry(&entries.borrow(), &key),
+            other => Err(SpadeError::runtime_error(format!("Map keys must be strings, got {}", other.type_name()), 0)),
+        };
+    }
+    let position = match index {
+        Value::Integer(n) if n >= 0 => n as usize,
+        other => return Err(SpadeError::runtime_error(format!("Index must be a non-negative integer, got {}", other.stringify()), 0)),
+    };
+    let out_of_bounds = |length: usize| {
+        SpadeError::runtime_error(format!("Index {} out of bounds for length {}", position, length), 0)
+    };
+    match object {
+        Value::List(elements) => {
+            let elements = elements.borrow();
+            elements.get(position).cloned().ok_or_else(|| out_of_bounds(elements.len()))
+        },
+        Value::String(s) => match s.chars().nth(position) {
+            Some(c) => Ok(Value::string(c.to_string())),
+            None => Err(out_of_bounds(s.chars().count())),
+        },
+        other => Err(SpadeError::runtime_error(format!("Cannot index into {}", other.type_name()), 0)),
+    }
+}
+
+fn literal_to_value(literal: &Literal) -> Value {
     match literal {
         Literal::Nil => Value::Nil,
-        Literal::Bool(b) => Value::Bool(b),
-        Literal::Number(n) => Value::Number(n),
-        Literal::String(s) => Value::String(s),
+        Literal::Bool(b) => Value::Bool(*b),
+        Literal::Integer(n) => Value::Integer(*n),
+        Literal::Number(n) => Value::Number(*n),
+        Literal::String(s) => Value::string(s.as_str()),
         _ => unreachable!()
     }
 }
 
-fn evaluate_binary(left: Value, op: BinaryOp, right: Value) -> Result<Value, SpadeError> {
+fn operand_error(op: BinaryOp, left: &Value, right: &Value) -> SpadeError {
+    SpadeError::runtime_error(
+        format!("cannot apply '{}' to {} and {}", op, left.type_name(), right.type_name()),
+        0,
+    )
+}
+
+// The operands of an arithmetic operator: two integers stay integers, and an
+// integer meeting a float is promoted to a float.
+enum Operands {
+    Integers(i64, i64),
+    Floats(f64, f64),
+}
+
+impl Operands {
+    fn of(left: &Value, right: &Value) -> Option<Operands> {
+        match (left, right) {
+            (Value::Integer(l), Value::Integer(r)) => Some(Operands::Integers(*l, *r)),
+            (Value::Integer(l), Value::Number(r)) => Some(Operands::Floats(*l as f64, *r)),
+            (Value::Number(l), Value::Integer(r)) => Some(Operands::Floats(*l, *r as f64)),
+            (Value::Number(l), Value::Number(r)) => Some(Operands::Floats(*l, *r)),
+            _ => None,
+        }
+    }
+
+    fn floats(self) -> (f64, f64) {
+        match self {
+            Operands::Integers(l, r) => (l as f64, r as f64),
+            Operands::Floats(l, r) => (l, r),
+        }
+    }
+}
+
+// Ranges are built as lists, so a longer one is refused rather than left to
+// exhaust memory.
+const MAX_RANGE_LENGTH: i64 = 1_000_000;
+
+fn integer_overflow(op: BinaryOp) -> SpadeError {
+    SpadeError::runtime_error(format!("Integer overflow in '{}'", op), 0)
+}
+
+pub(crate) fn evaluate_binary(left: Value, op: BinaryOp, right: Value) -> Result<Value, SpadeError> {
     match op {
-        BinaryOp::Plus => {
-            match (left, right) {
-                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
-                _ => Err(SpadeError::runtime_error("Invalid operands for +".to_string(), 0)),
+        BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Multiply => {
+            let integer = |l: i64, r: i64| match op {
+                BinaryOp::Plus => l.checked_add(r),
+                BinaryOp::Minus => l.checked_sub(r),
+                _ => l.checked_mul(r),
+            };
+            let float = |l: f64, r: f64| match op {
+                BinaryOp::Plus => l + r,
+                BinaryOp::Minus => l - r,
+                _ => l * r,
+            };
+            match Operands::of(&left, &right) {
+                Some(Operands::Integers(l, r)) => integer(l, r).map(Value::Integer).ok_or_else(|| integer_overflow(op)),
+                Some(Operands::Floats(l, r)) => Ok(Value::Number(float(l, r))),
+                None => Err(operand_error(op, &left, &right)),
             }
         },
-        BinaryOp::Minus => {
-            match (left, right) {
-                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l - r)),
-                _ => Err(SpadeError::runtime_error("Invalid operands for -".to_string(), 0)),
-            }
+        // An integer raised to a non-negative integer power stays an integer.
+        BinaryOp::Power => match Operands::of(&left, &right) {
+            Some(Operands::Integers(l, r)) if r >= 0 => {
+                let power = u32::try_from(r).ok().and_then(|r| l.checked_pow(r));
+                power.map(Value::Integer).ok_or_else(|| integer_overflow(op))
+            },
+            Some(Operands::Integers(l, r)) => Ok(Value::Number((l as f64).powf(r as f64))),
+            Some(Operands::Floats(l, r)) => Ok(Value::Number(l.powf(r))),
+            None => Err(operand_error(op, &left, &right)),
+        },
+        // Division is always float division, so `7 / 2` is 3.5.
+        BinaryOp::Divide => match Operands::of(&left, &right).map(Operands::floats) {
+            Some((_, 0.0)) => Err(SpadeError::runtime_error("Division by zero".to_string(), 0)),
+            Some((l, r)) => Ok(Value::Number(l / r)),
+            None => Err(operand_error(op, &left, &right)),
         },
-        BinaryOp::Multiply => {
-            match (left, right) {
-                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l * r)),
-                _ => Err(SpadeError::runtime_error("Invalid operands for *".to_string(), 0)),
+        // Ordered through `PartialOrd`, so `sort` and the operators agree:
+        // numbers by value and strings lexicographically, as Rust orders
+        // them. A NaN operand is unordered, which makes every comparison false.
+        BinaryOp::Greater | BinaryOp::GreaterEqual | BinaryOp::Less | BinaryOp::LessEqual => {
+            let strings = matches!((&left, &right), (Value::String(_), Value::String(_)));
+            if !strings && Operands::of(&left, &right).is_none() {
+                return Err(operand_error(op, &left, &right));
             }
+            let ordering = left.partial_cmp(&right);
+            Ok(Value::Bool(match op {
+                BinaryOp::Greater => ordering == Some(Ordering::Greater),
+                BinaryOp::GreaterEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                BinaryOp::Less => ordering == Some(Ordering::Less),
+                _ => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            }))
         },
-        BinaryOp::Divide => {
-            match (left, right) {
-                (Value::Number(l), Value::Number(r)) => {
-                    if r == 0.0 {
-                        Err(SpadeError::runtime_error("Division by zero".to_string(), 0))
-                    } else {
-                        Ok(Value::Number(l / r))
+        BinaryOp::Range => {
+            match (&left, &right) {
+                // Ranges are eager: `0..3` is the list `[0, 1, 2]`.
+                (Value::Integer(start), Value::Integer(end)) => {
+                    if end.saturating_sub(*start) > MAX_RANGE_LENGTH {
+                        return Err(SpadeError::runtime_error(
+                            format!("Range {}..{} is longer than {} elements", start, end, MAX_RANGE_LENGTH),
+                            0,
+                        ));
                     }
+                    Ok(Value::list((*start..*end).map(Value::Integer).collect()))
                 },
-                _ => Err(SpadeError::runtime_error("Invalid operands for /".to_string(), 0)),
+                (Value::Integer(_) | Value::Number(_), Value::Integer(_) | Value::Number(_)) => {
+                    Err(SpadeError::runtime_error("Range bounds must be integers".to_string(), 0))
+                },
+                _ => Err(operand_error(op, &left, &right)),
             }
         },
-        _ => Err(SpadeError::runtime_error("Unsupported binary operator".to_string(), 0)),
+        // Structural equality; values of different types are simply unequal,
+        // except that integers equal floats of the same value, and functions
+        // never compare equal.
+        BinaryOp::EqualEqual => Ok(Value::Bool(left == right)),
+        BinaryOp::NotEqual => Ok(Value::Bool(left != right)),
+        _ => Err(SpadeError::runtime_error(format!("Unsupported binary operator '{}'", op), 0)),
     }
 }
 
@@ -217,23 +1019,77 @@ mod tests {
     fn test_literal_evaluation() {
         let expr = Expr::Literal(Literal::Number(42.0));
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Number(42.0)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Number(42.0)));
 
         let expr = Expr::Literal(Literal::String("hello".to_string()));
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::String(ref s) if s == "hello"), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::String(ref s) if &**s == "hello"));
 
         let expr = Expr::Literal(Literal::Bool(true));
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Bool(true)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Bool(true)));
 
         let expr = Expr::Literal(Literal::Nil);
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Nil), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Nil));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Value::Nil.to_string(), "nil");
+        assert_eq!(Value::Bool(true).to_string(), "true");
+        assert_eq!(Value::Bool(false).to_string(), "false");
+        assert_eq!(Value::Integer(3).to_string(), "3");
+        assert_eq!(Value::Number(3.0).to_string(), "3.0");
+        assert_eq!(Value::Number(-0.25).to_string(), "-0.25");
+        assert_eq!(Value::string("a \"b\"").to_string(), "a \"b\"");
+        let function = SpadeFn::new(Some("f".to_string()), vec![], Rc::new(Statement::Block(vec![])), Environment::new());
+        assert_eq!(Value::function(function).to_string(), "<fn f>");
+        let function = SpadeFn::new(None, vec![], Rc::new(Statement::Block(vec![])), Environment::new());
+        assert_eq!(Value::function(function).to_string(), "<fn>");
+        let native: NativeFunction = |_, _| Ok(Value::Nil);
+        assert_eq!(Value::NativeFn(NativeFn::new("len", native)).to_string(), "<native fn len>");
+        let list = Value::list(vec![Value::Integer(1), Value::string("x")]);
+        assert_eq!(list.to_string(), "[1, \"x\"]");
+        assert_eq!(Value::map(IndexMap::from([("k".to_string(), list)])).to_string(), "{\"k\": [1, \"x\"]}");
+    }
+
+    #[test]
+    fn test_number_formatting() {
+        assert_eq!(evaluate_source("0.1 + 0.2").unwrap().to_string(), "0.3");
+        assert_eq!(Value::Number(1000000.0).to_string(), "1000000.0");
+        assert_eq!(Value::Number(-0.0).to_string(), "-0.0");
+        assert_eq!(Value::Number(0.0001).to_string(), "0.0001");
+        assert_eq!(Value::Number(1.0 / 3.0).to_string(), "0.333333333333333");
+        assert_eq!(Value::Number(-123.456).to_string(), "-123.456");
+        assert_eq!(Value::Number(0.0000001).to_string(), "0.0000001");
+        // Beyond the thresholds numbers switch to exponent form.
+        assert_eq!(Value::Number(1e21).to_string(), "1e21");
+        assert_eq!(Value::Number(-2.5e300).to_string(), "-2.5e300");
+        assert_eq!(Value::Number(1.5e-8).to_string(), "1.5e-8");
+        assert_eq!(Value::Number(f64::INFINITY).to_string(), "inf");
+        assert_eq!(Value::Number(f64::NAN).to_string(), "NaN");
+    }
+
+    #[test]
+    fn test_deep_clone_copies_nested_collections() {
+        let inner = Value::list(vec![Value::Number(1.0)]);
+        let original = Value::map(IndexMap::from([("xs".to_string(), inner.clone())]));
+        let copy = original.deep_clone();
+        assert_eq!(copy, original);
+
+        let Value::Map(entries) = &copy else { panic!("expected a map") };
+        let Value::List(elements) = entries.borrow()["xs"].clone() else { panic!("expected a list") };
+        elements.borrow_mut().push(Value::Number(2.0));
+        entries.borrow_mut().insert("extra".to_string(), Value::Nil);
+
+        assert_eq!(inner, Value::list(vec![Value::Number(1.0)]));
+        let Value::Map(entries) = &original else { unreachable!() };
+        assert_eq!(entries.borrow().len(), 1);
     }
 
     #[test]
@@ -243,40 +1099,44 @@ mod tests {
             left: Box::new(Expr::Literal(Literal::Number(3.0))),
             op: BinaryOp::Plus,
             right: Box::new(Expr::Literal(Literal::Number(4.0))),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Number(7.0)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Number(7.0)));
 
         // Test subtraction
         let expr = Expr::Binary {
             left: Box::new(Expr::Literal(Literal::Number(10.0))),
             op: BinaryOp::Minus,
             right: Box::new(Expr::Literal(Literal::Number(3.0))),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Number(7.0)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Number(7.0)));
 
         // Test multiplication
         let expr = Expr::Binary {
             left: Box::new(Expr::Literal(Literal::Number(6.0))),
             op: BinaryOp::Multiply,
             right: Box::new(Expr::Literal(Literal::Number(7.0))),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Number(42.0)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Number(42.0)));
 
         // Test division
         let expr = Expr::Binary {
             left: Box::new(Expr::Literal(Literal::Number(15.0))),
             op: BinaryOp::Divide,
             right: Box::new(Expr::Literal(Literal::Number(3.0))),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Number(5.0)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Number(5.0)));
     }
     #[test]
     fn test_division_by_zero() {
@@ -284,9 +1144,10 @@ mod tests {
             left: Box::new(Expr::Literal(Literal::Number(10.0))),
             op: BinaryOp::Divide,
             right: Box::new(Expr::Literal(Literal::Number(0.0))),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env);
+        let result = evaluate_expression(&expr, &mut env);
         assert!(result.is_err());
         // assert_eq!(result.unwrap_err(), SpadeError::runtime_error("Division by zero".to_string(), 0));
     }
@@ -296,10 +1157,11 @@ mod tests {
         let expr = Expr::Unary {
             op: UnaryOp::Minus,
             expr: Box::new(Expr::Literal(Literal::Number(42.0))),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Number(-42.0)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Number(-42.0)));
     }
 
     #[test]
@@ -308,44 +1170,60 @@ mod tests {
         let expr = Expr::Unary {
             op: UnaryOp::Not,
             expr: Box::new(Expr::Literal(Literal::Bool(true))),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Bool(false)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Bool(false)));
 
         let expr = Expr::Unary {
             op: UnaryOp::Not,
             expr: Box::new(Expr::Literal(Literal::Bool(false))),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Bool(true)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Bool(true)));
 
         // Test with nil (should return true)
         let expr = Expr::Unary {
             op: UnaryOp::Not,
             expr: Box::new(Expr::Literal(Literal::Nil)),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Bool(true)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Bool(true)));
 
         // Test with number (should return false)
         let expr = Expr::Unary {
             op: UnaryOp::Not,
             expr: Box::new(Expr::Literal(Literal::Number(42.0))),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Bool(false)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Bool(false)));
+    }
+
+    #[test]
+    fn test_not_follows_truthiness() {
+        let negations = [
+            ("!nil", true), ("!false", true), ("!true", false), ("!0", false), ("!-1", false),
+            ("!\"\"", false), ("!\"x\"", false), ("![]", false), ("!{}", false),
+            ("!fn() {}", false), ("!!0", true),
+        ];
+        for (source, expected) in negations {
+            assert_eq!(evaluate_source(source).unwrap(), Value::Bool(expected), "{}", source);
+        }
     }
 
     #[test]
     fn test_grouping() {
         let expr = Expr::Grouping(Box::new(Expr::Literal(Literal::Number(42.0))));
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Number(42.0)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Number(42.0)));
     }
 
     #[test]
@@ -355,9 +1233,10 @@ mod tests {
             left: Box::new(Expr::Literal(Literal::String("hello".to_string()))),
             op: BinaryOp::Minus,
             right: Box::new(Expr::Literal(Literal::Number(5.0))),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env);
+        let result = evaluate_expression(&expr, &mut env);
         assert!(result.is_err());
         // assert_eq!(result.unwrap_err(), SpadeError::runtime_error("Invalid operands for -".to_string(), 0));
 
@@ -365,9 +1244,10 @@ mod tests {
         let expr = Expr::Unary {
             op: UnaryOp::Minus,
             expr: Box::new(Expr::Literal(Literal::String("hello".to_string()))),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env);
+        let result = evaluate_expression(&expr, &mut env);
         assert!(result.is_err());
         // assert_eq!(result.unwrap_err(), SpadeError::runtime_error("Invalid operand for unary -".to_string(), 0));
     }
@@ -380,13 +1260,213 @@ mod tests {
                 left: Box::new(Expr::Literal(Literal::Number(3.0))),
                 op: BinaryOp::Plus,
                 right: Box::new(Expr::Literal(Literal::Number(4.0))),
+                line: Line(1),
             }))),
             op: BinaryOp::Multiply,
             right: Box::new(Expr::Literal(Literal::Number(2.0))),
+            line: Line(1),
         };
         let mut env = Environment::new();
-        let result = evaluate_expression(expr, &mut env).unwrap();
-        assert_eq!(matches!(result, Value::Number(14.0)), true);
+        let result = evaluate_expression(&expr, &mut env).unwrap();
+        assert!(matches!(result, Value::Number(14.0)));
+    }
+
+    fn evaluate_source(source: &str) -> Result<Value, SpadeError> {
+        let tokens = crate::token::scan_tokens(source.to_string()).unwrap();
+        let expr = crate::tree::parse(tokens).unwrap();
+        let mut env = Environment::new();
+        evaluate_expression(&expr, &mut env)
+    }
+
+    #[test]
+    fn test_number_methods() {
+        assert!(matches!(evaluate_source("(3.7).floor()").unwrap(), Value::Number(n) if n == 3.0));
+        assert!(matches!(evaluate_source("3.2.ceil()").unwrap(), Value::Number(n) if n == 4.0));
+        assert_eq!(evaluate_source("(5).to_string()").unwrap(), Value::string("5"));
+    }
+
+    #[test]
+    fn test_bool_methods() {
+        assert_eq!(evaluate_source("true.not()").unwrap(), Value::Bool(false));
+        assert_eq!(evaluate_source("false.to_string()").unwrap(), Value::string("false"));
+    }
+
+    #[test]
+    fn test_unknown_method() {
+        match evaluate_source("(1.5).shout()") {
+            Err(SpadeError::RuntimeError { message, .. }) => {
+                assert!(message.contains("number"));
+                assert!(message.contains("shout"));
+            },
+            other => panic!("expected runtime error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_spread_arguments() {
+        let mut spade = crate::Spade::new();
+        spade.run("fn sum(a, b, c) { return a + b + c; }").unwrap();
+        assert!(matches!(spade.run("sum(...[1, 2, 3]);").unwrap(), Value::Integer(6)));
+        assert!(matches!(spade.run("sum(10, ...[20], 30);").unwrap(), Value::Integer(60)));
+        assert!(matches!(spade.run("let xs = [1, 2]; sum(...xs, 3);").unwrap(), Value::Integer(6)));
+        assert!(spade.run("sum(...[1, 2]);").is_err());
+        assert!(spade.run("sum(...1);").is_err());
+    }
+
+    fn error_message(result: Result<Value, SpadeError>) -> String {
+        match result {
+            Err(SpadeError::RuntimeError { message, .. }) => message,
+            other => panic!("expected runtime error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_operand_errors_name_types() {
+        assert_eq!(error_message(evaluate_source("\"a\" + 1")), "cannot apply '+' to string and integer");
+        assert_eq!(error_message(evaluate_source("nil * true")), "cannot apply '*' to nil and bool");
+        assert_eq!(error_message(evaluate_source("[1] / 2")), "cannot apply '/' to list and integer");
+        assert_eq!(error_message(evaluate_source("-\"a\"")), "cannot apply unary '-' to string");
+    }
+
+    #[test]
+    fn test_partial_ord() {
+        assert!(Value::Number(2.0) < Value::Number(10.0));
+        assert!(Value::string("10") < Value::string("2"));
+        assert_eq!(Value::Number(1.0).partial_cmp(&Value::string("1")), None);
+        assert_eq!(Value::Nil.partial_cmp(&Value::Number(0.0)), None);
+        assert_eq!(Value::Number(f64::NAN).partial_cmp(&Value::Number(f64::NAN)), None);
+    }
+
+    #[test]
+    fn test_equality_operators() {
+        assert_eq!(evaluate_source("1 == 1").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate_source("\"a\" != \"b\"").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate_source("nil == nil").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate_source("true == true").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate_source("1 == \"1\"").unwrap(), Value::Bool(false));
+        assert_eq!(evaluate_source("nil != false").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate_source("[1, \"a\"] == [1, \"a\"]").unwrap(), Value::Bool(true));
+
+        let mut spade = crate::Spade::new();
+        spade.run("fn f() {}").unwrap();
+        assert_eq!(spade.run("f == f;").unwrap(), Value::Bool(false));
+        assert_eq!(spade.run("byte_len == byte_len;").unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        assert_eq!(evaluate_source("3 < 4").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate_source("5 >= 5").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate_source("5 > 5").unwrap(), Value::Bool(false));
+        assert_eq!(evaluate_source("4 <= 3").unwrap(), Value::Bool(false));
+        assert_eq!(evaluate_source("\"apple\" < \"banana\"").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate_source("\"b\" > \"a\"").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate_source("\"ab\" <= \"abc\"").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate_source("\"Z\" >= \"a\"").unwrap(), Value::Bool(false));
+        assert_eq!(error_message(evaluate_source("1 < \"2\"")), "cannot apply '<' to integer and string");
+        assert_eq!(error_message(evaluate_source("\"2\" > 1.5")), "cannot apply '>' to string and number");
+        assert_eq!(error_message(evaluate_source("nil >= 0")), "cannot apply '>=' to nil and integer");
+    }
+
+    #[test]
+    fn test_indexing() {
+        assert!(matches!(evaluate_source("[1, 2, 3][2]").unwrap(), Value::Integer(3)));
+        assert_eq!(evaluate_source("\"héllo\"[1]").unwrap(), Value::string("é"));
+        assert_eq!(evaluate_source("\"日本語\"[2]").unwrap(), Value::string("語"));
+        assert!(evaluate_source("\"héllo\"[5]").is_err());
+        assert!(evaluate_source("[1][0.5]").is_err());
+        assert!(evaluate_source("1[0]").is_err());
+    }
+
+    #[test]
+    fn test_map_literal_keeps_insertion_order() {
+        let map = evaluate_source("{\"b\": 1, \"a\": \"x\", \"c\": [true]}").unwrap();
+        assert_eq!(map.stringify(), "{\"b\": 1, \"a\": \"x\", \"c\": [true]}");
+        assert!(evaluate_source("{1: 2}").is_err());
+    }
+
+    #[test]
+    fn test_power() {
+        assert!(matches!(evaluate_source("-2 ** 2").unwrap(), Value::Integer(-4)));
+        assert!(matches!(evaluate_source("(-2) ** 2").unwrap(), Value::Integer(4)));
+        assert!(matches!(evaluate_source("2 ** 3 ** 2").unwrap(), Value::Integer(512)));
+        assert_eq!(evaluate_source("2 ** -1").unwrap(), Value::Number(0.5));
+        assert_eq!(evaluate_source("2 ** 0.5").unwrap(), Value::Number(2f64.sqrt()));
+        assert!(matches!(evaluate_source("(2 ** 3) ** 2").unwrap(), Value::Integer(64)));
+    }
+
+    #[test]
+    fn test_integer_arithmetic_promotes_to_float() {
+        assert!(matches!(evaluate_source("2 + 3").unwrap(), Value::Integer(5)));
+        assert!(matches!(evaluate_source("7 / 2").unwrap(), Value::Number(n) if n == 3.5));
+        assert!(matches!(evaluate_source("-7 / 2").unwrap(), Value::Number(n) if n == -3.5));
+        assert!(matches!(evaluate_source("2 ** 10").unwrap(), Value::Integer(1024)));
+        assert!(matches!(evaluate_source("1 + 2.5").unwrap(), Value::Number(n) if n == 3.5));
+        assert!(matches!(evaluate_source("7.0 / 2").unwrap(), Value::Number(n) if n == 3.5));
+        assert!(matches!(evaluate_source("2 * 1f").unwrap(), Value::Number(n) if n == 2.0));
+        assert!(matches!(evaluate_source("1 / 3 * 3").unwrap(), Value::Number(n) if n == 1.0));
+        assert_eq!(evaluate_source("1 == 1.0").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate_source("2 < 2.5").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate_source("(3).to_string()").unwrap(), Value::string("3"));
+        assert_eq!(evaluate_source("(3.0).to_string()").unwrap(), Value::string("3.0"));
+        assert_eq!(evaluate_source("6 / 2").unwrap().stringify(), "3.0");
+        assert_eq!(evaluate_source("6.0 / 2").unwrap().stringify(), "3.0");
+    }
+
+    #[test]
+    fn test_number_suffixes_stringify_differently() {
+        let (int, float) = (evaluate_source("10i").unwrap(), evaluate_source("10f").unwrap());
+        assert!(matches!(int, Value::Integer(10)));
+        assert!(matches!(float, Value::Number(n) if n == 10.0));
+        assert_eq!((int.stringify(), float.stringify()), ("10".to_string(), "10.0".to_string()));
+    }
+
+    #[test]
+    fn test_integer_overflow_is_an_error() {
+        assert_eq!(error_message(evaluate_source("9223372036854775807 + 1")), "Integer overflow in '+'");
+        assert_eq!(error_message(evaluate_source("2 ** 64")), "Integer overflow in '**'");
+        assert!(evaluate_source("1 / 0").is_err());
+        assert!(matches!(evaluate_source("9223372036854775807.0 + 1").unwrap(), Value::Number(_)));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_indexes_must_be_integers() {
+        assert!(matches!(evaluate_source("[1, 2, 3][1]").unwrap(), Value::Integer(2)));
+        assert_eq!(error_message(evaluate_source("[1, 2, 3][1.0]")), "Index must be a non-negative integer, got 1.0");
+        assert!(evaluate_source("\"abc\"[0.0]").is_err());
+        assert!(evaluate_source("0..2.0").is_err());
+        assert_eq!(evaluate_source("0..3").unwrap(), evaluate_source("[0, 1, 2]").unwrap());
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        assert_eq!(evaluate_source(r#""x=${1+1}""#).unwrap(), Value::string("x=2"));
+        assert_eq!(evaluate_source(r#""${"a"}, ${[1, "b"]}, ${nil} and ${2.5 * 2}""#).unwrap(), Value::string("a, [1, \"b\"], nil and 5.0"));
+        assert_eq!(evaluate_source(r#""outer ${"inner ${1 > 0}"}""#).unwrap(), Value::string("outer inner true"));
+        assert_eq!(evaluate_source(r#""\${1+1} costs $2""#).unwrap(), Value::string("${1+1} costs $2"));
+        assert!(evaluate_source(r#""${undefined}""#).is_err());
+
+        let mut spade = crate::Spade::new();
+        assert_eq!(spade.run("let name = \"spade\"; \"hi ${name}, ${len(name)} letters\";").unwrap(), Value::string("hi spade, 5 letters"));
+    }
+
+    #[test]
+    fn test_let_expression() {
+        assert!(matches!(evaluate_source("let x = 2 in x * x").unwrap(), Value::Integer(4)));
+        assert!(matches!(evaluate_source("1 + (let x = 2 in x * 10)").unwrap(), Value::Integer(21)));
+
+        let mut spade = crate::Spade::new();
+        assert!(matches!(spade.run("let x = 2 in x * x;").unwrap(), Value::Integer(4)));
+        assert!(spade.run("x;").is_err());
+    }
+
+    #[test]
+    fn test_pretty() {
+        let value = evaluate_source("{\"name\": \"spade\", \"tags\": [1, [2], []], \"meta\": {}}").unwrap();
+        assert_eq!(
+            value.pretty(0),
+            "{\n  \"name\": \"spade\",\n  \"tags\": [\n    1,\n    [\n      2\n    ],\n    []\n  ],\n  \"meta\": {}\n}",
+        );
+        assert_eq!(Value::string("x").pretty(0), "x");
+    }
+}
@@ -1,11 +1,14 @@
 use std::fmt;
+use std::rc::Rc;
 
 use crate::token::Token;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BinaryOp {
     Multiply,
     Divide,
+    Power,
     Plus,
     Minus,
     Greater,
@@ -15,7 +18,8 @@ pub enum BinaryOp {
     NotEqual,
     EqualEqual,
     And,
-    Or
+    Or,
+    Range,
 }
 
 impl fmt::Display for BinaryOp {
@@ -23,6 +27,7 @@ impl fmt::Display for BinaryOp {
         match self {
             BinaryOp::Multiply => write!(f, "*"),
             BinaryOp::Divide => write!(f, "/"),
+            BinaryOp::Power => write!(f, "**"),
             BinaryOp::Plus => write!(f, "+"),
             BinaryOp::Minus => write!(f, "-"),
             BinaryOp::Greater => write!(f, ">"),
@@ -33,11 +38,13 @@ impl fmt::Display for BinaryOp {
             BinaryOp::EqualEqual => write!(f, "=="),
             BinaryOp::And => write!(f, "and"),
             BinaryOp::Or => write!(f, "or"),
+            BinaryOp::Range => write!(f, ".."),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum UnaryOp {
     Minus,
     Not 
@@ -52,45 +59,112 @@ impl fmt::Display for UnaryOp {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Literal {
     Nil,
+    Integer(i64),
     Number(f64),
     String(String),
     Bool(bool),
-    Var(Token),
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_var"))]
+    Var(Token, Resolution),
 }
 
 impl fmt::Display for Literal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Literal::Nil => write!(f, "nil"),
+            Literal::Integer(n) => write!(f, "{}", n),
             Literal::Number(n) => write!(f, "{}", n),
             Literal::String(s) => write!(f, "\"{}\"", s),
             Literal::Bool(b) => write!(f, "{}", b),
-            Literal::Var(b) => write!(f, "getvar {}", b.lexeme),
+            Literal::Var(b, _) => write!(f, "getvar {}", b.lexeme),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+/// The source line an expression came from, for runtime error messages.
+///
+/// Like a token's line it is position metadata, not part of what the
+/// expression means, so any two lines compare equal. That keeps ASTs parsed
+/// from differently laid out source equal.
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Line(pub usize);
+
+impl PartialEq for Line {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+/// Where the resolver found a variable's declaration, so the interpreter can
+/// go straight to the right scope. Like `Line` it is filled in after parsing
+/// and doesn't affect equality.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Resolution {
+    /// Not resolved yet: every scope is searched, innermost first.
+    #[default]
+    Unresolved,
+    /// Declared this many scopes out from the use.
+    Local(usize),
+    /// Not declared in any enclosing local scope.
+    Global,
+}
+
+impl PartialEq for Resolution {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Expr {
-    Binary { left: Box<Expr>, op: BinaryOp, right: Box<Expr> },
-    Unary { op: UnaryOp, expr: Box<Expr> },
+    Binary { left: Box<Expr>, op: BinaryOp, right: Box<Expr>, line: Line },
+    Unary { op: UnaryOp, expr: Box<Expr>, line: Line },
     Literal(Literal),
     Grouping(Box<Expr>),
-    Assign { token: Token, value: Box<Expr> },
-    Call { callee: Box<Expr>, arguments: Vec<Expr> },
+    Assign {
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_token"))]
+        token: Token,
+        value: Box<Expr>,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        resolution: Resolution,
+    },
+    Call { callee: Box<Expr>, arguments: Vec<Expr>, line: Line },
+    Get {
+        object: Box<Expr>,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_token"))]
+        name: Token,
+    },
+    List(Vec<Expr>),
+    Spread(Box<Expr>),
+    Index { object: Box<Expr>, index: Box<Expr>, line: Line },
+    Map(Vec<(Expr, Expr)>),
+    /// `let name = value in body`: binds `name` only while evaluating `body`.
+    Let { name: String, value: Box<Expr>, body: Box<Expr> },
+    /// Evaluates only the branch selected by `condition`.
+    Conditional { condition: Box<Expr>, then_branch: Box<Expr>, else_branch: Box<Expr> },
+    /// An anonymous function, `fn (a, b) { ... }`.
+    Function { parameters: Vec<String>, body: Rc<Statement> },
+    /// A string with embedded expressions, `"x is ${x}"`: string literals for
+    /// the text between the expressions, concatenated with them in order.
+    Interpolation(Vec<Expr>),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Statement {
     Expression(Expr),
-    Print(Expr),
+    /// `print a, b, c;` prints its values on one line, separated by spaces.
+    Print(Vec<Expr>),
     Block(Vec<Statement>),
     VarDec {
         name: String,
         initializer: Option<Expr>,
+        mutable: bool,
     },
     If {
         condition: Expr,
@@ -100,18 +174,77 @@ pub enum Statement {
     Fn {
         name: String,
         parameters: Vec<String>,
-        body: Box<Statement>,
+        body: Rc<Statement>,
     },
     Return(Option<Expr>),
+    /// `increment` runs after every iteration, including ones ended early by
+    /// `continue`; it is set when desugaring `for` loops.
+    While {
+        condition: Expr,
+        body: Box<Statement>,
+        increment: Option<Expr>,
+    },
+    /// `for (name in iterable) body` runs `body` once per element of a list
+    /// or character of a string, with `name` bound in a fresh scope.
+    ForIn {
+        name: String,
+        iterable: Expr,
+        body: Box<Statement>,
+        line: Line,
+    },
+    Break,
+    Continue,
+    /// `import "path";` runs another file's top-level statements in the
+    /// global scope. The path is relative to the importing file.
+    Import {
+        path: String,
+        line: Line,
+    },
+    /// `match (subject) { pattern if guard => body ... }` runs the body of
+    /// the first arm whose pattern equals the subject and whose guard, if
+    /// any, is truthy. Nothing runs when no arm matches.
+    Match {
+        subject: Expr,
+        arms: Vec<MatchArm>,
+    },
+    /// `with (name = resource) body`: runs `body` with `name` bound and then
+    /// calls the resource's registered cleanup, even if `body` fails.
+    With {
+        name: String,
+        resource: Expr,
+        body: Box<Statement>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MatchArm {
+    /// `None` for the `_` wildcard, which matches anything.
+    pub pattern: Option<Expr>,
+    pub guard: Option<Expr>,
+    pub body: Statement,
+}
+
+impl fmt::Display for MatchArm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.pattern {
+            Some(pattern) => write!(f, "(arm {}", pattern)?,
+            None => write!(f, "(arm _")?,
+        }
+        if let Some(guard) = &self.guard {
+            write!(f, " (if {})", guard)?;
+        }
+        write!(f, " {})", self.body)
+    }
 }
 
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Expr::Binary { left, op, right } => {
+            Expr::Binary { left, op, right, .. } => {
                 write!(f, "({} {} {})", left, op, right)
             },
-            Expr::Unary { op, expr } => {
+            Expr::Unary { op, expr, .. } => {
                 write!(f, "({}{})", op, expr)
             },
             Expr::Literal(literal) => {
@@ -120,12 +253,39 @@ impl fmt::Display for Expr {
             Expr::Grouping(expr) => {
                 write!(f, "(group {})", expr)
             },
-            Expr::Assign { token, value } => {
+            Expr::Assign { token, value, .. } => {
                 write!(f, "(assign {} {})", token.lexeme, value)
             },
-            Expr::Call { callee, arguments } => {
+            Expr::Call { callee, arguments, .. } => {
                 write!(f, "(call {} {})", callee, arguments.iter().map(|a| a.to_string()).collect::<Vec<String>>().join(", "))
             },
+            Expr::Get { object, name } => {
+                write!(f, "(get {} {})", object, name.lexeme)
+            },
+            Expr::List(elements) => {
+                write!(f, "(list {})", elements.iter().map(|e| e.to_string()).collect::<Vec<String>>().join(" "))
+            },
+            Expr::Spread(expr) => {
+                write!(f, "(spread {})", expr)
+            },
+            Expr::Index { object, index, .. } => {
+                write!(f, "(index {} {})", object, index)
+            },
+            Expr::Map(entries) => {
+                write!(f, "(map {})", entries.iter().map(|(k, v)| format!("({} {})", k, v)).collect::<Vec<String>>().join(" "))
+            },
+            Expr::Conditional { condition, then_branch, else_branch } => {
+                write!(f, "(? {} {} {})", condition, then_branch, else_branch)
+            },
+            Expr::Let { name, value, body } => {
+                write!(f, "(let {} {} {})", name, value, body)
+            },
+            Expr::Function { parameters, body } => {
+                write!(f, "(lambda {} {})", parameters.join(", "), body)
+            },
+            Expr::Interpolation(parts) => {
+                write!(f, "(interpolate {})", parts.iter().map(|p| p.to_string()).collect::<Vec<String>>().join(" "))
+            },
         }
     }
 }
@@ -133,10 +293,11 @@ impl fmt::Display for Expr {
 impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Statement::VarDec { name, initializer } => {
+            Statement::VarDec { name, initializer, mutable } => {
+                let keyword = if *mutable { "var mut" } else { "var" };
                 match initializer {
-                    Some(expr) => write!(f, "(var {} {})", name, expr),
-                    None => write!(f, "(var {})", name),
+                    Some(expr) => write!(f, "({} {} {})", keyword, name, expr),
+                    None => write!(f, "({} {})", keyword, name),
                 }
             },
             Statement::Block(statements) => {
@@ -145,11 +306,26 @@ impl fmt::Display for Statement {
             Statement::Expression(expr) => {
                 write!(f, "(expr {})", expr)
             },
-            Statement::Print(expr) => {
-                write!(f, "(print {})", expr)
+            Statement::Print(exprs) => {
+                write!(f, "(print {})", exprs.iter().map(|e| e.to_string()).collect::<Vec<String>>().join(" "))
             },
             Statement::If { condition, then_branch, else_branch } => {
-                write!(f, "(if {} {} {})", condition, then_branch, else_branch.as_ref().map(|b| b.to_string()).unwrap_or("".to_string()))
+                write!(f, "(if {} {}", condition, then_branch)?;
+                // Flatten `else if` chains into sibling `elif` clauses.
+                let mut else_branch = else_branch;
+                while let Some(branch) = else_branch {
+                    match branch.as_ref() {
+                        Statement::If { condition, then_branch, else_branch: next } => {
+                            write!(f, " (elif {} {})", condition, then_branch)?;
+                            else_branch = next;
+                        },
+                        other => {
+                            write!(f, " (else {})", other)?;
+                            break;
+                        },
+                    }
+                }
+                write!(f, ")")
             },
             Statement::Fn { name, parameters, body } => {
                 write!(f, "(fn {} {} {})", name, parameters.iter().map(|p| p.to_string()).collect::<Vec<String>>().join(", "), body)
@@ -160,6 +336,323 @@ impl fmt::Display for Statement {
                     None => write!(f, "(return)"),
                 }
             },
+            Statement::While { condition, body, increment } => {
+                match increment {
+                    Some(increment) => write!(f, "(while {} {} {})", condition, body, increment),
+                    None => write!(f, "(while {} {})", condition, body),
+                }
+            },
+            Statement::ForIn { name, iterable, body, .. } => {
+                write!(f, "(for {} {} {})", name, iterable, body)
+            },
+            Statement::Break => write!(f, "(break)"),
+            Statement::Continue => write!(f, "(continue)"),
+            Statement::Import { path, .. } => write!(f, "(import \"{}\")", path),
+            Statement::Match { subject, arms } => {
+                write!(f, "(match {}", subject)?;
+                for arm in arms {
+                    write!(f, " {}", arm)?;
+                }
+                write!(f, ")")
+            },
+            Statement::With { name, resource, body } => {
+                write!(f, "(with {} {} {})", name, resource, body)
+            },
+        }
+    }
+}
+
+// Tokens in the AST are names, so only their text is serialized.
+#[cfg(feature = "serde")]
+fn serialize_token<S: serde::Serializer>(token: &Token, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&token.lexeme)
+}
+
+// A variable is serialized as just its name; the resolution is derived data.
+#[cfg(feature = "serde")]
+fn serialize_var<S: serde::Serializer>(token: &Token, _: &Resolution, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_token(token, serializer)
+}
+
+/// Renders a parsed program as JSON, for tooling and debugging.
+#[cfg(feature = "serde")]
+pub fn ast_to_json(stmts: &[Statement]) -> String {
+    serde_json::to_string(stmts).expect("the AST always serializes")
+}
+
+/// Walks an AST. Each method is called for the matching node and by default
+/// visits the node's children, so an implementation only overrides the
+/// methods for the nodes it cares about. `visit_statement` and `visit_expr`
+/// see every node before it is dispatched to its specific method.
+pub trait Visitor {
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_expression_statement(&mut self, expr: &Expr) {
+        expr.accept(self);
+    }
+
+    fn visit_print(&mut self, exprs: &[Expr]) {
+        exprs.iter().for_each(|expr| expr.accept(self));
+    }
+
+    fn visit_block(&mut self, statements: &[Statement]) {
+        statements.iter().for_each(|statement| statement.accept(self));
+    }
+
+    fn visit_var_dec(&mut self, _name: &str, initializer: Option<&Expr>, _mutable: bool) {
+        if let Some(initializer) = initializer {
+            initializer.accept(self);
+        }
+    }
+
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Statement, else_branch: Option<&Statement>) {
+        condition.accept(self);
+        then_branch.accept(self);
+        if let Some(else_branch) = else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_fn(&mut self, _name: &str, _parameters: &[String], body: &Statement) {
+        body.accept(self);
+    }
+
+    fn visit_return(&mut self, value: Option<&Expr>) {
+        if let Some(value) = value {
+            value.accept(self);
         }
     }
+
+    fn visit_while(&mut self, condition: &Expr, body: &Statement, increment: Option<&Expr>) {
+        condition.accept(self);
+        body.accept(self);
+        if let Some(increment) = increment {
+            increment.accept(self);
+        }
+    }
+
+    fn visit_for_in(&mut self, _name: &str, iterable: &Expr, body: &Statement) {
+        iterable.accept(self);
+        body.accept(self);
+    }
+
+    fn visit_break(&mut self) {}
+
+    fn visit_continue(&mut self) {}
+
+    fn visit_import(&mut self, _path: &str) {}
+
+    fn visit_match(&mut self, subject: &Expr, arms: &[MatchArm]) {
+        subject.accept(self);
+        for arm in arms {
+            if let Some(pattern) = &arm.pattern {
+                pattern.accept(self);
+            }
+            if let Some(guard) = &arm.guard {
+                guard.accept(self);
+            }
+            arm.body.accept(self);
+        }
+    }
+
+    fn visit_with(&mut self, _name: &str, resource: &Expr, body: &Statement) {
+        resource.accept(self);
+        body.accept(self);
+    }
+
+    fn visit_binary(&mut self, left: &Expr, _op: BinaryOp, right: &Expr) {
+        left.accept(self);
+        right.accept(self);
+    }
+
+    fn visit_unary(&mut self, _op: UnaryOp, expr: &Expr) {
+        expr.accept(self);
+    }
+
+    fn visit_literal(&mut self, _literal: &Literal) {}
+
+    fn visit_grouping(&mut self, expr: &Expr) {
+        expr.accept(self);
+    }
+
+    fn visit_assign(&mut self, _name: &Token, value: &Expr) {
+        value.accept(self);
+    }
+
+    fn visit_call(&mut self, callee: &Expr, arguments: &[Expr]) {
+        callee.accept(self);
+        arguments.iter().for_each(|argument| argument.accept(self));
+    }
+
+    fn visit_get(&mut self, object: &Expr, _name: &Token) {
+        object.accept(self);
+    }
+
+    fn visit_list(&mut self, elements: &[Expr]) {
+        elements.iter().for_each(|element| element.accept(self));
+    }
+
+    fn visit_spread(&mut self, expr: &Expr) {
+        expr.accept(self);
+    }
+
+    fn visit_index(&mut self, object: &Expr, index: &Expr) {
+        object.accept(self);
+        index.accept(self);
+    }
+
+    fn visit_map(&mut self, entries: &[(Expr, Expr)]) {
+        for (key, value) in entries {
+            key.accept(self);
+            value.accept(self);
+        }
+    }
+
+    fn visit_let(&mut self, _name: &str, value: &Expr, body: &Expr) {
+        value.accept(self);
+        body.accept(self);
+    }
+
+    fn visit_conditional(&mut self, condition: &Expr, then_branch: &Expr, else_branch: &Expr) {
+        condition.accept(self);
+        then_branch.accept(self);
+        else_branch.accept(self);
+    }
+
+    fn visit_function(&mut self, _parameters: &[String], body: &Statement) {
+        body.accept(self);
+    }
+
+    fn visit_interpolation(&mut self, parts: &[Expr]) {
+        parts.iter().for_each(|part| part.accept(self));
+    }
+}
+
+/// Calls the `Visitor` method specific to the kind of `statement`.
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::Expression(expr) => visitor.visit_expression_statement(expr),
+        Statement::Print(exprs) => visitor.visit_print(exprs),
+        Statement::Block(statements) => visitor.visit_block(statements),
+        Statement::VarDec { name, initializer, mutable } => visitor.visit_var_dec(name, initializer.as_ref(), *mutable),
+        Statement::If { condition, then_branch, else_branch } => {
+            visitor.visit_if(condition, then_branch, else_branch.as_deref())
+        },
+        Statement::Fn { name, parameters, body } => visitor.visit_fn(name, parameters, body),
+        Statement::Return(value) => visitor.visit_return(value.as_ref()),
+        Statement::While { condition, body, increment } => visitor.visit_while(condition, body, increment.as_ref()),
+        Statement::ForIn { name, iterable, body, .. } => visitor.visit_for_in(name, iterable, body),
+        Statement::Break => visitor.visit_break(),
+        Statement::Continue => visitor.visit_continue(),
+        Statement::Import { path, .. } => visitor.visit_import(path),
+        Statement::Match { subject, arms } => visitor.visit_match(subject, arms),
+        Statement::With { name, resource, body } => visitor.visit_with(name, resource, body),
+    }
+}
+
+/// Calls the `Visitor` method specific to the kind of `expr`.
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Binary { left, op, right, .. } => visitor.visit_binary(left, *op, right),
+        Expr::Unary { op, expr, .. } => visitor.visit_unary(*op, expr),
+        Expr::Literal(literal) => visitor.visit_literal(literal),
+        Expr::Grouping(expr) => visitor.visit_grouping(expr),
+        Expr::Assign { token, value, .. } => visitor.visit_assign(token, value),
+        Expr::Call { callee, arguments, .. } => visitor.visit_call(callee, arguments),
+        Expr::Get { object, name } => visitor.visit_get(object, name),
+        Expr::List(elements) => visitor.visit_list(elements),
+        Expr::Spread(expr) => visitor.visit_spread(expr),
+        Expr::Index { object, index, .. } => visitor.visit_index(object, index),
+        Expr::Map(entries) => visitor.visit_map(entries),
+        Expr::Let { name, value, body } => visitor.visit_let(name, value, body),
+        Expr::Conditional { condition, then_branch, else_branch } => {
+            visitor.visit_conditional(condition, then_branch, else_branch)
+        },
+        Expr::Function { parameters, body } => visitor.visit_function(parameters, body),
+        Expr::Interpolation(parts) => visitor.visit_interpolation(parts),
+    }
+}
+
+impl Statement {
+    pub fn accept<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.visit_statement(self);
+    }
+}
+
+impl Expr {
+    pub fn accept<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.visit_expr(self);
+    }
+}
+
+/// Counts every statement and expression node in a program, e.g. to reject
+/// overly complex untrusted scripts before running them.
+pub fn ast_size(stmts: &[Statement]) -> usize {
+    struct Counter(usize);
+
+    impl Visitor for Counter {
+        fn visit_statement(&mut self, statement: &Statement) {
+            self.0 += 1;
+            walk_statement(self, statement);
+        }
+
+        fn visit_expr(&mut self, expr: &Expr) {
+            self.0 += 1;
+            walk_expr(self, expr);
+        }
+    }
+
+    let mut counter = Counter(0);
+    stmts.iter().for_each(|statement| statement.accept(&mut counter));
+    counter.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::scan_tokens;
+    use crate::tree::parse_stmt;
+
+    #[test]
+    fn test_ast_size() {
+        // let (1) + binary (1) + two literals (2) = 4
+        // if (1) + var (1) + block (1) + print (1) + call (1) + callee (1) + argument (1) = 7
+        let statements = parse_stmt(scan_tokens("let x = 1 + 2; if (x) { print f(x); }".to_string()).unwrap()).unwrap();
+        assert_eq!(ast_size(&statements), 11);
+        assert_eq!(ast_size(&[]), 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_ast_to_json() {
+        let statements = parse_stmt(scan_tokens("1 + 2; print x;".to_string()).unwrap()).unwrap();
+        let json = ast_to_json(&statements);
+        assert!(json.contains("\"Binary\""), "{}", json);
+        assert!(json.contains("\"op\":\"Plus\""), "{}", json);
+        assert!(json.contains("{\"Print\":[{\"Literal\":{\"Var\":\"x\"}}]}"), "{}", json);
+    }
+
+    #[test]
+    fn test_visitor_counts_literals() {
+        struct LiteralCounter(usize);
+
+        impl Visitor for LiteralCounter {
+            fn visit_literal(&mut self, _literal: &Literal) {
+                self.0 += 1;
+            }
+        }
+
+        let source = "let x = 1 + 2; fn f(a) { return a * -3; } print f([x, \"y\"]);";
+        let statements = parse_stmt(scan_tokens(source.to_string()).unwrap()).unwrap();
+        let mut counter = LiteralCounter(0);
+        statements.iter().for_each(|statement| statement.accept(&mut counter));
+        // 1, 2, a, 3, f, x and "y"
+        assert_eq!(counter.0, 7);
+    }
 }
@@ -0,0 +1,141 @@
+use crate::evaluate::{evaluate_binary, Value};
+use crate::expressions::{BinaryOp, Expr, Literal, UnaryOp};
+
+/// Collapses constant sub-expressions into literals, so `2 + 3 * 4` becomes
+/// `14` before the program runs.
+///
+/// Only operators on number, bool and string literals are folded. Anything
+/// involving a variable or a call is left for runtime, as is any operation
+/// that would fail or give a non-finite number (division by zero, say), so
+/// it still reports its error where it happens. Function bodies are not
+/// entered.
+pub fn fold_constants(expr: Expr) -> Expr {
+    match expr {
+        // `and`/`or` yield an operand rather than a bool, so they stay as written.
+        Expr::Binary { left, op: op @ (BinaryOp::And | BinaryOp::Or), right, line } => Expr::Binary {
+            left: Box::new(fold_constants(*left)),
+            op,
+            right: Box::new(fold_constants(*right)),
+            line,
+        },
+        Expr::Binary { left, op, right, line } => {
+            let left = fold_constants(*left);
+            let right = fold_constants(*right);
+            if let (Some(l), Some(r)) = (constant(&left), constant(&right))
+                && op != BinaryOp::Range
+                && let Ok(value) = evaluate_binary(l, op, r)
+                && let Some(literal) = to_literal(value)
+            {
+                return Expr::Literal(literal);
+            }
+            Expr::Binary { left: Box::new(left), op, right: Box::new(right), line }
+        },
+        Expr::Unary { op, expr, line } => {
+            let expr = fold_constants(*expr);
+            match (op, constant(&expr)) {
+                (UnaryOp::Minus, Some(Value::Integer(n))) if n != i64::MIN => Expr::Literal(Literal::Integer(-n)),
+                (UnaryOp::Minus, Some(Value::Number(n))) => Expr::Literal(Literal::Number(-n)),
+                (UnaryOp::Not, Some(value)) => Expr::Literal(Literal::Bool(!value.is_truthy())),
+                _ => Expr::Unary { op, expr: Box::new(expr), line },
+            }
+        },
+        // A literal never needs its parentheses.
+        Expr::Grouping(expr) => match fold_constants(*expr) {
+            literal @ Expr::Literal(_) => literal,
+            expr => Expr::Grouping(Box::new(expr)),
+        },
+        Expr::Literal(literal) => Expr::Literal(literal),
+        Expr::Assign { token, value, resolution } => {
+            Expr::Assign { token, value: Box::new(fold_constants(*value)), resolution }
+        },
+        Expr::Call { callee, arguments, line } => Expr::Call {
+            callee: Box::new(fold_constants(*callee)),
+            arguments: arguments.into_iter().map(fold_constants).collect(),
+            line,
+        },
+        Expr::Get { object, name } => Expr::Get { object: Box::new(fold_constants(*object)), name },
+        Expr::List(elements) => Expr::List(elements.into_iter().map(fold_constants).collect()),
+        Expr::Interpolation(parts) => Expr::Interpolation(parts.into_iter().map(fold_constants).collect()),
+        Expr::Spread(expr) => Expr::Spread(Box::new(fold_constants(*expr))),
+        Expr::Index { object, index, line } => Expr::Index {
+            object: Box::new(fold_constants(*object)),
+            index: Box::new(fold_constants(*index)),
+            line,
+        },
+        Expr::Map(entries) => {
+            Expr::Map(entries.into_iter().map(|(key, value)| (fold_constants(key), fold_constants(value))).collect())
+        },
+        Expr::Let { name, value, body } => Expr::Let {
+            name,
+            value: Box::new(fold_constants(*value)),
+            body: Box::new(fold_constants(*body)),
+        },
+        Expr::Conditional { condition, then_branch, else_branch } => Expr::Conditional {
+            condition: Box::new(fold_constants(*condition)),
+            then_branch: Box::new(fold_constants(*then_branch)),
+            else_branch: Box::new(fold_constants(*else_branch)),
+        },
+        function @ Expr::Function { .. } => function,
+    }
+}
+
+// The value of a foldable literal. `nil` is excluded because the interpreter
+// can be configured to propagate it through operators.
+fn constant(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Literal(Literal::Integer(n)) => Some(Value::Integer(*n)),
+        Expr::Literal(Literal::Number(n)) => Some(Value::Number(*n)),
+        Expr::Literal(Literal::Bool(b)) => Some(Value::Bool(*b)),
+        Expr::Literal(Literal::String(s)) => Some(Value::string(s.as_str())),
+        _ => None,
+    }
+}
+
+fn to_literal(value: Value) -> Option<Literal> {
+    match value {
+        Value::Integer(n) => Some(Literal::Integer(n)),
+        Value::Number(n) if n.is_finite() => Some(Literal::Number(n)),
+        Value::Bool(b) => Some(Literal::Bool(b)),
+        Value::String(s) => Some(Literal::String(s.to_string())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::scan_tokens;
+    use crate::tree::parse_stmt;
+    use crate::expressions::Statement;
+
+    fn parse(source: &str) -> Expr {
+        let statements = parse_stmt(scan_tokens(format!("{};", source)).unwrap()).unwrap();
+        let Some(Statement::Expression(expr)) = statements.into_iter().next() else { panic!() };
+        expr
+    }
+
+    fn folds_to(source: &str, expected: &str) {
+        assert_eq!(fold_constants(parse(source)), parse(expected), "folding {}", source);
+    }
+
+    #[test]
+    fn test_folds_pure_arithmetic() {
+        folds_to("2 + 3", "5");
+        assert_eq!(fold_constants(parse("-4")), Expr::Literal(Literal::Integer(-4)));
+        assert_eq!(fold_constants(parse("-4.5")), Expr::Literal(Literal::Number(-4.5)));
+        folds_to("(1 + 2) * 3 - 2 ** 3 / 4", "7.0");
+        folds_to("1 < 2 == !false", "true");
+        folds_to("[1 + 1, f(2 * 3)]", "[2, f(6)]");
+    }
+
+    #[test]
+    fn test_leaves_runtime_work_alone() {
+        folds_to("x + 1", "x + 1");
+        folds_to("x + 2 * 3", "x + 6");
+        folds_to("f() * 2", "f() * 2");
+        folds_to("1 / 0", "1 / 0");
+        folds_to("1 / (2 - 2)", "1 / 0");
+        folds_to("1 + \"a\"", "1 + \"a\"");
+        folds_to("nil or 1 + 1", "nil or 2");
+    }
+}
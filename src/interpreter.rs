@@ -1,47 +1,207 @@
+use std::io::BufRead;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
 use crate::error::SpadeError;
 use crate::expressions::Statement;
-use crate::evaluate::{evaluate_expression, evaluate_statement, Value};
+use crate::evaluate::{self, evaluate_statement, print_values, NativeFn, NativeFunction, Value};
 use crate::environment::Environment;
+use crate::natives::define_natives;
+use crate::runtime::{ErrorSink, OutputSink};
+use crate::resolver::resolve;
+use crate::spade::parse_source;
 
 pub struct Interpreter  {
     env: Environment,
 }
 
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Interpreter {
     pub fn new() -> Self {
-        Interpreter {
-            env: Environment::new(),
+        let mut env = Environment::new();
+        define_natives(&mut env);
+        Interpreter { env }
+    }
+
+    /// Enables strict math, where any arithmetic producing NaN or infinity
+    /// is reported as a runtime error instead of yielding the value.
+    pub fn with_strict_math(self, enabled: bool) -> Self {
+        self.env.runtime_mut().strict_math = enabled;
+        self
+    }
+
+    /// With strict division (the default), dividing by zero is a runtime
+    /// error. Without it, division follows IEEE-754 and yields infinity or
+    /// NaN, which strict math still rejects.
+    pub fn with_strict_division(self, enabled: bool) -> Self {
+        self.env.runtime_mut().strict_division = enabled;
+        self
+    }
+
+    /// Enables SQL-style nil propagation: arithmetic or comparison with a nil
+    /// operand evaluates to nil instead of raising an error.
+    pub fn with_nil_propagation(self, enabled: bool) -> Self {
+        self.env.runtime_mut().nil_propagation = enabled;
+        self
+    }
+
+    /// Like `run`, but with errors rendered as messages. The result is the
+    /// value of a trailing expression statement; other statements yield nil.
+    pub fn interpret(&mut self, statements: Vec<Statement>) -> Result<Value, String> {
+        match self.run(statements) {
+            Ok(value) => Ok(value),
+            Err(SpadeError::RuntimeError { message, line }) => Err(format!("{} at line {}", message, line)),
+            Err(e @ (SpadeError::ScanError { .. } | SpadeError::ParseError { .. })) => Err(e.to_string()),
+            Err(SpadeError::Return(_)) => unreachable!(),
+            // The parser only accepts these inside loops, so they can only
+            // escape from hand-built statements.
+            Err(SpadeError::Break) | Err(SpadeError::Continue) => Err("'break' or 'continue' outside of a loop".to_string()),
         }
     }
 
-    pub fn interpret(&mut self, statements: Vec<Statement>) -> Result<(), String> {
+    /// Executes the statements in order, returning the value of the last one.
+    /// A top-level `return` ends the script early and its value is the result.
+    pub fn run(&mut self, mut statements: Vec<Statement>) -> Result<Value, SpadeError> {
+        resolve(&mut statements)?;
+        let mut last = Value::Nil;
         for statement in statements {
-            self.execute(statement)?;
+            let echo = self.env.runtime().echo_expressions && matches!(statement, Statement::Expression(_));
+            last = match evaluate_statement(&statement, &mut self.env) {
+                Ok(value) => value,
+                Err(SpadeError::Return(value)) => return Ok(value),
+                Err(e) => return Err(e),
+            };
+            if echo && last != Value::Nil {
+                print_values(std::slice::from_ref(&last), &self.env)?;
+            }
         }
-        Ok(())
-    }
-
-    fn execute(&mut self, statement: Statement) -> Result<(), String> {
-        evaluate_statement(statement, &mut self.env).map_err(|e| match e {
-            SpadeError::RuntimeError { message, line } => format!("{} at line {}", message, line),
-            SpadeError::Return(_) => unreachable!(),
-        })?;
-        Ok(())
-    }
-
-    fn stringify(&self, value: Value) -> String {
-        match value {
-            Value::Nil => "nil".to_string(),
-            Value::Bool(b) => b.to_string(),
-            Value::Number(n) => {
-                if n.fract() == 0.0 {
-                    format!("{:.0}", n)
-                } else {
-                    n.to_string()
-                }
-            },
-            Value::String(s) => s,
-            Value::Function(function) => format!("fn {:?}", function),
+        Ok(last)
+    }
+
+    /// Reads, runs and echoes one line of `input` at a time until it ends.
+    /// Prompts and results go to the output sink, errors to the error sink,
+    /// and a failing line doesn't end the session. A line that doesn't parse
+    /// is retried with a trailing `;`, so `1 + 2` works like `1 + 2;`.
+    pub fn repl(&mut self, mut input: impl BufRead) -> Result<(), SpadeError> {
+        let echo = std::mem::replace(&mut self.env.runtime_mut().echo_expressions, true);
+        let result = self.repl_lines(&mut input);
+        self.env.runtime_mut().echo_expressions = echo;
+        result
+    }
+
+    fn repl_lines(&mut self, input: &mut impl BufRead) -> Result<(), SpadeError> {
+        let io_error = |e: std::io::Error| SpadeError::runtime_error(format!("REPL I/O failed: {}", e), 0);
+        let (output, errors) = {
+            let runtime = self.env.runtime();
+            (runtime.output.clone(), runtime.error_sink.clone())
+        };
+        loop {
+            write!(output.borrow_mut(), "> ").map_err(io_error)?;
+            output.borrow_mut().flush().map_err(io_error)?;
+            let mut line = String::new();
+            if input.read_line(&mut line).map_err(io_error)? == 0 {
+                return Ok(());
+            }
+            let statements = parse_source(&line).or_else(|e| parse_source(&format!("{};", line.trim_end())).map_err(|_| e));
+            if let Err(e) = statements.and_then(|statements| self.run(statements)) {
+                writeln!(errors.borrow_mut(), "{}", e).map_err(io_error)?;
+            }
+        }
+    }
+
+    /// Runs a source file in the global scope, even if it has already been
+    /// run or imported.
+    pub fn run_file(&mut self, path: &Path) -> Result<Value, SpadeError> {
+        evaluate::run_file(path, &mut self.env)
+    }
+
+    /// Enables the interactive debugger opened by `breakpoint()`.
+    pub fn with_debug(self, enabled: bool) -> Self {
+        self.env.runtime_mut().debug = enabled;
+        self
+    }
+
+    /// Includes captured variables when printing functions, for debugging.
+    pub fn with_debug_print(self, enabled: bool) -> Self {
+        self.env.runtime_mut().debug_print = enabled;
+        self
+    }
+
+    /// Overrides how `print` renders values, e.g. to show numbers as currency.
+    pub fn with_formatter(self, formatter: impl Fn(&Value) -> String + 'static) -> Self {
+        self.env.runtime_mut().formatter = Some(Rc::new(formatter));
+        self
+    }
+
+    /// Logs each function call with its arguments and each return with its
+    /// value to the error sink, indented by call depth.
+    pub fn with_trace_calls(self, enabled: bool) -> Self {
+        self.env.runtime_mut().trace_calls = enabled;
+        self
+    }
+
+    /// Prints the value of each non-nil top-level expression statement, the
+    /// way a REPL shows results. Off by default, which suits scripts.
+    pub fn with_echo_expressions(self, enabled: bool) -> Self {
+        self.env.runtime_mut().echo_expressions = enabled;
+        self
+    }
+
+    /// Sets how deeply calls may nest before failing with a stack overflow
+    /// error; 1000 by default. Each nested call uses about 10 KiB of native
+    /// stack in a debug build and about 4 KiB in a release build, so the
+    /// default needs a thread stack of roughly 10 MiB; the 2 MiB a spawned
+    /// thread gets by default only covers about 200 calls in a debug build.
+    /// Hosts on smaller stacks should lower the limit to match.
+    pub fn with_max_call_depth(self, depth: usize) -> Self {
+        self.env.runtime_mut().max_call_depth = depth;
+        self
+    }
+
+    /// Records the cumulative wall-clock time spent in each user-defined
+    /// function, for `profile_report`.
+    pub fn with_profiling(self, enabled: bool) -> Self {
+        self.env.runtime_mut().profiling = enabled;
+        self
+    }
+
+    /// Time spent in each function called so far while profiling, slowest
+    /// first. A function's time includes the functions it calls.
+    pub fn profile_report(&self) -> Vec<(String, Duration)> {
+        let mut report: Vec<(String, Duration)> = self.env.runtime().profile.iter()
+            .map(|(name, elapsed)| (name.clone(), *elapsed))
+            .collect();
+        report.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        report
+    }
+
+    /// Redirects `print` output, which goes to stdout by default.
+    pub fn with_writer(self, writer: OutputSink) -> Self {
+        self.env.runtime_mut().output = writer;
+        self
+    }
+
+    /// Redirects diagnostics such as call traces, which go to stderr by default.
+    pub fn with_error_sink(self, sink: ErrorSink) -> Self {
+        self.env.runtime_mut().error_sink = sink;
+        self
+    }
+
+    /// Makes a native function callable by name from Spade code.
+    pub fn define_native(&mut self, name: &str, function: NativeFunction) {
+        self.env.define(name.to_string(), Value::NativeFn(NativeFn::new(name, function)));
+    }
+
+    pub fn stringify(&self, value: Value) -> String {
+        match &value {
+            Value::Function(fun) if self.env.runtime().debug_print => format!("{} {}", value.stringify(), fun.captures()),
+            _ => value.stringify(),
         }
     }
 }
@@ -49,14 +209,15 @@ impl Interpreter {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{expressions::{BinaryOp, Expr, Literal}, token::scan_tokens, tree::parse_stmt};
+    use crate::{expressions::{BinaryOp, Expr, Line, Literal}, token::scan_tokens, tree::parse_stmt, Spade};
 
     #[test]
     fn test_print_statement() {
-        let mut interpreter = Interpreter::new();
-        let statement = Statement::Print(Expr::Literal(Literal::String("Hello, World!".to_string())));
+        let (mut interpreter, printed) = recording_interpreter();
+        let statement = Statement::Print(vec![Expr::Literal(Literal::String("Hello, World!".to_string()))]);
         let result = interpreter.interpret(vec![statement]);
         assert!(result.is_ok());
+        assert_eq!(printed.lines(), vec!["Hello, World!"]);
     }
 
     #[test]
@@ -67,41 +228,55 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_interpret_returns_last_expression_value() {
+        let (mut interpreter, printed) = recording_interpreter();
+        assert!(matches!(interpreter.interpret(program("let x = 2; x + 2;")), Ok(Value::Integer(4))));
+        assert_eq!(interpreter.interpret(program("x + 2; let y = x;")), Ok(Value::Nil));
+        assert_eq!(interpreter.interpret(program("print x;")), Ok(Value::Nil));
+        assert_eq!(printed.lines(), vec!["2"]);
+    }
+
     #[test]
     fn test_multiple_statements() {
-        let mut interpreter = Interpreter::new();
+        let (mut interpreter, printed) = recording_interpreter();
         let statements = vec![
-            Statement::Print(Expr::Literal(Literal::Number(1.0))),
-            Statement::Print(Expr::Literal(Literal::Bool(true))),
+            Statement::Print(vec![Expr::Literal(Literal::Number(1.0))]),
+            Statement::Print(vec![Expr::Literal(Literal::Bool(true))]),
             Statement::Expression(Expr::Literal(Literal::Nil)),
         ];
         let result = interpreter.interpret(statements);
         assert!(result.is_ok());
+        assert_eq!(printed.lines(), vec!["1.0", "true"]);
     }
 
     #[test]
+    #[allow(clippy::approx_constant)] // 3.14 is a sample value, not an approximation of pi
     fn test_stringify_values() {
         let interpreter = Interpreter::new();
         
         assert_eq!(interpreter.stringify(Value::Nil), "nil");
         assert_eq!(interpreter.stringify(Value::Bool(true)), "true");
         assert_eq!(interpreter.stringify(Value::Bool(false)), "false");
-        assert_eq!(interpreter.stringify(Value::Number(42.0)), "42");
+        assert_eq!(interpreter.stringify(Value::Integer(42)), "42");
+        assert_eq!(interpreter.stringify(Value::Number(42.0)), "42.0");
         assert_eq!(interpreter.stringify(Value::Number(3.14)), "3.14");
-        assert_eq!(interpreter.stringify(Value::String("hello".to_string())), "hello");
+        assert_eq!(interpreter.stringify(Value::string("hello")), "hello");
     }
 
     #[test]
     fn test_complex_expression() {
-        let mut interpreter = Interpreter::new();
+        let (mut interpreter, printed) = recording_interpreter();
         let expr = Expr::Binary {
             left: Box::new(Expr::Literal(Literal::Number(3.0))),
             op: BinaryOp::Plus,
             right: Box::new(Expr::Literal(Literal::Number(4.0))),
+            line: Line(1),
         };
-        let statement = Statement::Print(expr);
+        let statement = Statement::Print(vec![expr]);
         let result = interpreter.interpret(vec![statement]);
         assert!(result.is_ok());
+        assert_eq!(printed.lines(), vec!["7.0"]);
     }
 
     #[test]
@@ -111,39 +286,264 @@ mod tests {
             left: Box::new(Expr::Literal(Literal::String("hello".to_string()))),
             op: BinaryOp::Minus,
             right: Box::new(Expr::Literal(Literal::Number(5.0))),
+            line: Line(1),
         };
-        let statement = Statement::Print(expr);
+        let statement = Statement::Print(vec![expr]);
         let result = interpreter.interpret(vec![statement]);
         assert!(result.is_err());
     }
 
     #[test]
     fn test_assign_and_print() {
-        let mut interpreter = Interpreter::new();
-        let code = "let x = 1; print x;".to_string();
-        let tokens = scan_tokens(code.to_string()).unwrap();
-        let statements = parse_stmt(tokens).unwrap();
-        let result = interpreter.interpret(statements);
-        assert!(result.is_ok());
+        assert_eq!(run_source("let x = 1; print x;"), vec!["1"]);
     }
 
     #[test]
     fn test_assign_and_print_2() {
-        let mut interpreter = Interpreter::new();
+        let (mut interpreter, printed) = recording_interpreter();
         let code = "let x = 1; { let y = 2; print x;} print y;".to_string();
         let tokens = scan_tokens(code.to_string()).unwrap();
         let statements = parse_stmt(tokens).unwrap();
         let result = interpreter.interpret(statements);
         assert!(result.is_err());
+        assert_eq!(printed.lines(), vec!["1"]);
     }
 
     #[test]
     fn test_if_statement() {
+        let code = "if (false) { print \"true\"; } else { print \"false\"; }";
+        assert_eq!(run_source(code), vec!["false"]);
+    }
+
+    fn overflowing_product() -> Statement {
+        Statement::Print(vec![Expr::Binary {
+            left: Box::new(Expr::Literal(Literal::Number(1e308))),
+            op: BinaryOp::Multiply,
+            right: Box::new(Expr::Literal(Literal::Number(10.0))),
+            line: Line(1),
+        }])
+    }
+
+    #[test]
+    fn test_infinity_allowed_by_default() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let result = interpreter.interpret(vec![overflowing_product()]);
+        assert!(result.is_ok());
+        assert_eq!(printed.lines(), vec!["inf"]);
+    }
+
+    #[test]
+    fn test_strict_math_rejects_infinity() {
+        let mut interpreter = Interpreter::new().with_strict_math(true);
+        let result = interpreter.interpret(vec![overflowing_product()]);
+        assert!(result.unwrap_err().contains("non-finite"));
+    }
+
+    #[test]
+    fn test_strict_division_by_default() {
+        let mut spade = Spade::new();
+        assert!(matches!(spade.run("1 / 0;"), Err(SpadeError::RuntimeError { .. })));
+        assert!(matches!(spade.run("0 / 0;"), Err(SpadeError::RuntimeError { .. })));
+    }
+
+    #[test]
+    fn test_ieee_division() {
+        let mut spade = Spade::with_interpreter(Interpreter::new().with_strict_division(false));
+        assert_eq!(spade.run("1 / 0;").unwrap(), Value::Number(f64::INFINITY));
+        assert_eq!(spade.run("-1 / 0;").unwrap(), Value::Number(f64::NEG_INFINITY));
+        assert!(matches!(spade.run("0 / 0;").unwrap(), Value::Number(n) if n.is_nan()));
+        assert_eq!(spade.run("6 / 3;").unwrap(), Value::Number(2.0));
+        assert_eq!(spade.run("str(-1 / 0);").unwrap(), Value::string("-inf"));
+
+        let mut spade = Spade::with_interpreter(Interpreter::new().with_strict_division(false).with_strict_math(true));
+        assert!(spade.run("1 / 0;").unwrap_err().to_string().contains("non-finite"));
+    }
+
+    fn make_adder() -> Vec<Statement> {
+        let code = "fn make() { let x = 10; fn adder(y) { return x + y; } return adder; } make();";
+        program(code)
+    }
+
+    #[test]
+    fn test_debug_print_shows_captures() {
+        let mut interpreter = Interpreter::new().with_debug_print(true);
+        let adder = interpreter.run(make_adder()).unwrap();
+        assert!(interpreter.stringify(adder).ends_with(" <captured: x=10>"));
+    }
+
+    #[test]
+    fn test_print_hides_captures_by_default() {
         let mut interpreter = Interpreter::new();
-        let code = "if (false) { print \"true\"; } else { print \"false\"; }".to_string();
-        let tokens = scan_tokens(code.to_string()).unwrap();
-        let statements = parse_stmt(tokens).unwrap();
-        let result = interpreter.interpret(statements);
+        let adder = interpreter.run(make_adder()).unwrap();
+        assert!(!interpreter.stringify(adder).contains("captured"));
+    }
+
+    #[test]
+    fn test_functions_print_their_name() {
+        let mut interpreter = Interpreter::new();
+        let adder = interpreter.run(make_adder()).unwrap();
+        assert_eq!(interpreter.stringify(adder), "<fn adder>");
+        let anonymous = interpreter.run(program("fn(a) { return a; };")).unwrap();
+        assert_eq!(interpreter.stringify(anonymous), "<fn>");
+    }
+
+    #[test]
+    fn test_custom_formatter() {
+        let (interpreter, printed) = recording_interpreter();
+        let mut interpreter = interpreter.with_formatter(|value| match value {
+            Value::Number(n) => format!("${:.2}", n),
+            other => other.stringify(),
+        });
+        let statements = program("print 3.0; print \"total\";");
+        interpreter.run(statements).unwrap();
+        assert_eq!(printed.lines(), vec!["$3.00".to_string(), "total".to_string()]);
+    }
+
+    // Output written to an interpreter's sink instead of stdout.
+    #[derive(Clone, Default)]
+    struct Printed(Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl Printed {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.borrow().clone()).unwrap().lines().map(str::to_string).collect()
+        }
+
+        fn clear(&self) {
+            self.0.borrow_mut().clear();
+        }
+    }
+
+    // An interpreter whose `print` output is recorded instead of written out.
+    fn recording_interpreter() -> (Interpreter, Printed) {
+        let printed = Printed::default();
+        let interpreter = Interpreter::new().with_writer(printed.0.clone());
+        (interpreter, printed)
+    }
+
+    // Scans and parses `code`, which must be valid.
+    fn program(code: &str) -> Vec<Statement> {
+        parse_stmt(scan_tokens(code.to_string()).unwrap()).unwrap()
+    }
+
+    // Runs `code` on a fresh interpreter and returns the lines it printed.
+    fn run_source(code: &str) -> Vec<String> {
+        let (mut interpreter, printed) = recording_interpreter();
+        interpreter.run(program(code)).unwrap();
+        printed.lines()
+    }
+
+    #[test]
+    fn test_repl_session() {
+        let output = Rc::new(std::cell::RefCell::new(Vec::<u8>::new()));
+        let errors = Rc::new(std::cell::RefCell::new(Vec::<u8>::new()));
+        let mut interpreter = Interpreter::new().with_writer(output.clone()).with_error_sink(errors.clone());
+        let input = "1 + 2\nlet x = 5;\nlet = ;\nprint x;\nx * 2;\nprint y;\n";
+        interpreter.repl(input.as_bytes()).unwrap();
+        assert_eq!(String::from_utf8(output.borrow().clone()).unwrap(), "> 3\n> > > 5\n> 10\n> > ");
+        let errors = String::from_utf8(errors.borrow().clone()).unwrap();
+        assert_eq!(errors.lines().count(), 2);
+        assert!(errors.contains("Undefined variable 'y'."));
+        // Echoing is only on for the session.
+        assert!(!interpreter.env.runtime().echo_expressions);
+    }
+
+    #[test]
+    fn test_echo_expressions() {
+        let (interpreter, printed) = recording_interpreter();
+        let mut interpreter = interpreter.with_echo_expressions(true);
+        let statements = program("let x = 2; x * 3; { x + 1; } print \"hi\"; nil;");
+        interpreter.run(statements).unwrap();
+        // Only the top-level, non-nil expression echoes; `print` prints as usual.
+        assert_eq!(printed.lines(), vec!["6".to_string(), "hi".to_string()]);
+    }
+
+    #[test]
+    fn test_no_echo_by_default() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let statements = program("1 + 2;");
+        assert!(matches!(interpreter.run(statements).unwrap(), Value::Integer(3)));
+        assert!(printed.lines().is_empty());
+    }
+
+    #[test]
+    fn test_run_file_does_not_echo() {
+        let path = std::env::temp_dir().join(format!("spade-echo-{}.spade", std::process::id()));
+        std::fs::write(&path, "1 + 2;").unwrap();
+        let (interpreter, printed) = recording_interpreter();
+        let mut interpreter = interpreter.with_echo_expressions(true);
+        assert!(matches!(interpreter.run_file(&path).unwrap(), Value::Integer(3)));
+        assert!(printed.lines().is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_to_list_of_string_and_range() {
+        let code = "print to_list(\"abc\"); print to_list(0..3); print 2..2; print join(to_list(\"spade\"), \"-\");";
+        assert_eq!(run_source(code), vec!["[\"a\", \"b\", \"c\"]", "[0, 1, 2]", "[]", "s-p-a-d-e"]);
+        let result = Interpreter::new().run(program("0..1.5;"));
+        assert!(matches!(result, Err(SpadeError::RuntimeError { .. })));
+    }
+
+    #[test]
+    fn test_overlong_range_is_an_error() {
+        let mut interpreter = Interpreter::new();
+        let code = "let r = 0\n  ..100000000000000;";
+        let result = interpreter.interpret(program(code));
+        assert_eq!(result.unwrap_err(), "Range 0..100000000000000 is longer than 1000000 elements at line 2");
+        assert!(interpreter.run(program("-1..999999;")).is_ok());
+    }
+
+    #[test]
+    fn test_match_guard_falls_through() {
+        let code = "let x = 5;
+            match (x) {
+                5 if x > 10 => print \"big five\";
+                1 => print \"one\";
+                5 => print \"five\";
+                _ => print \"other\";
+            }
+            match (\"y\") { \"x\" => print \"x\"; }";
+        assert_eq!(run_source(code), vec!["five"]);
+    }
+
+    #[test]
+    fn test_match_wildcard_guard() {
+        let code = "fn sign(n) { match (n) { 0 => return \"zero\"; _ if n > 0 => return \"positive\"; _ => return \"negative\"; } }
+            print sign(0); print sign(3); print sign(-2);";
+        assert_eq!(run_source(code), vec!["zero", "positive", "negative"]);
+    }
+
+    #[test]
+    fn test_top_level_return_ends_script() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let statements = program("print 1; return 42; print 2;");
+        assert!(matches!(interpreter.run(statements).unwrap(), Value::Integer(42)));
+        assert_eq!(printed.lines(), vec!["1".to_string()]);
+    }
+
+    #[test]
+    fn test_bare_return_exits_function() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = "fn f() { return; print 1; } f(); print 2;";
+        let statements = program(code);
+        assert!(interpreter.run(statements).is_ok());
+        assert_eq!(printed.lines(), vec!["2".to_string()]);
+    }
+
+    #[test]
+    fn test_native_call() {
+        assert_eq!(run_source("print debug_ast(\"1+2\");"), vec!["(1 + 2)"]);
+    }
+
+    #[test]
+    fn test_unless_statement() {
+        let mut interpreter = Interpreter::new();
+        let run = |interpreter: &mut Interpreter, code: &str| {
+            interpreter.run(program(code)).unwrap()
+        };
+        assert!(matches!(run(&mut interpreter, "unless (false) 1;"), Value::Integer(1)));
+        assert_eq!(run(&mut interpreter, "unless (true) 1;"), Value::Nil);
+        assert!(matches!(run(&mut interpreter, "unless (true) 1; else 2;"), Value::Integer(2)));
     }
 
     #[test]
@@ -153,6 +553,444 @@ mod tests {
         let tokens = scan_tokens(code.to_string()).unwrap();
         let statements = parse_stmt(tokens).unwrap();
         let result = interpreter.interpret(statements);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_with_runs_cleanup_on_completion_and_error() {
+        let setup = "fn close(r) { print \"closed\"; } let res = on_close([1], close);";
+
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = format!("{} with (r = res) {{ print r; }} print \"after\";", setup);
+        interpreter.run(program(&code)).unwrap();
+        assert_eq!(printed.lines(), vec!["[1]", "closed", "after"]);
+
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = format!("{} with (r = res) {{ print r; print r - 1; print \"unreachable\"; }}", setup);
+        let result = interpreter.run(program(&code));
+        assert!(matches!(result, Err(SpadeError::RuntimeError { .. })));
+        assert_eq!(printed.lines(), vec!["[1]", "closed"]);
     }
-}
 
+    #[test]
+    fn test_with_keeps_the_body_error_over_the_cleanup_error() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = "fn close(r) { print \"closing\"; return r - 1; }\nwith (r = on_close(\"db\", close)) {\n  print r * 2;\n}";
+        let result = interpreter.interpret(program(code));
+        assert!(result.unwrap_err().ends_with("at line 3"));
+        assert_eq!(printed.lines(), vec!["closing"]);
+
+        // Without a body error, the cleanup's is reported.
+        let code = "with (r = on_close(\"db\", close)) { print r; }";
+        let result = interpreter.interpret(program(code));
+        assert!(result.unwrap_err().ends_with("at line 1"));
+    }
+
+    #[test]
+    fn test_with_matches_cleanups_by_identity() {
+        let code = "fn first(r) { print \"first\"; } fn second(r) { print \"second\"; }
+            let a = on_close(\"db\", first); let b = on_close(\"db\", second);
+            with (r = b) {} with (r = a) {} with (r = \"db\") {}";
+        assert_eq!(run_source(code), vec!["second", "first"]);
+    }
+
+    #[test]
+    fn test_trace_calls() {
+        let sink = Rc::new(std::cell::RefCell::new(Vec::<u8>::new()));
+        let mut interpreter = Interpreter::new().with_trace_calls(true).with_error_sink(sink.clone());
+        let code = "fn inner(x) { return x * 2; } fn outer(x) { return inner(x) + 1; } outer(3);";
+        interpreter.run(program(code)).unwrap();
+        let trace = String::from_utf8(sink.borrow().clone()).unwrap();
+        assert_eq!(trace, "-> outer(3)\n  -> inner(3)\n  <- inner = 6\n<- outer = 7\n");
+    }
+
+    #[test]
+    fn test_print_to_writer() {
+        let output = Rc::new(std::cell::RefCell::new(Vec::<u8>::new()));
+        let mut interpreter = Interpreter::new().with_writer(output.clone());
+        interpreter.run(program("print \"hi\"; print 42;")).unwrap();
+        assert_eq!(String::from_utf8(output.borrow().clone()).unwrap(), "hi\n42\n");
+    }
+
+    #[test]
+    fn test_print_several_values() {
+        let output = Rc::new(std::cell::RefCell::new(Vec::<u8>::new()));
+        let mut interpreter = Interpreter::new().with_writer(output.clone());
+        let code = "print 1, 2, \"x\"; print \"only\"; print [1, 2], nil, 1 + 1;";
+        interpreter.run(program(code)).unwrap();
+        assert_eq!(String::from_utf8(output.borrow().clone()).unwrap(), "1 2 x\nonly\n[1, 2] nil 2\n");
+        assert!(parse_stmt(scan_tokens("print 1,;".to_string()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_print_representation_of_each_value() {
+        let output = Rc::new(std::cell::RefCell::new(Vec::<u8>::new()));
+        let mut interpreter = Interpreter::new().with_writer(output.clone());
+        let code = "fn f() {}
+            print nil; print true; print false; print 42; print 3.14; print -0.5; print \"hi\";
+            print [1, \"a\", nil]; print {\"k\": [2]}; print f; print byte_len;";
+        interpreter.run(program(code)).unwrap();
+        let printed = String::from_utf8(output.borrow().clone()).unwrap();
+        let expected = ["nil", "true", "false", "42", "3.14", "-0.5", "hi", "[1, \"a\", nil]", "{\"k\": [2]}", "<fn f>", "<native fn byte_len>"];
+        assert_eq!(printed.lines().collect::<Vec<&str>>(), expected);
+    }
+
+    #[test]
+    fn test_profiling() {
+        let mut interpreter = Interpreter::new().with_profiling(true);
+        let code = "fn fib(n) { if (n < 2) { return n; } return fib(n - 1) + fib(n - 2); }
+            fn idle() {}
+            for (let i = 0; i < 20; i = i + 1) { fib(10); }";
+        interpreter.run(program(code)).unwrap();
+        let report = interpreter.profile_report();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].0, "fib");
+        assert!(report[0].1 > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_profiling_off_by_default() {
+        let mut interpreter = Interpreter::new();
+        interpreter.run(program("fn f() {} f();")).unwrap();
+        assert!(interpreter.profile_report().is_empty());
+    }
+
+    #[test]
+    fn test_trace_calls_off_by_default() {
+        let sink = Rc::new(std::cell::RefCell::new(Vec::<u8>::new()));
+        let mut interpreter = Interpreter::new().with_error_sink(sink.clone());
+        interpreter.run(program("fn f() { return 1; } f();")).unwrap();
+        assert!(sink.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_while_loop() {
+        let code = "let mut i = 0; while (i < 3) { print i; i = i + 1; } print i;";
+        assert_eq!(run_source(code), vec!["0", "1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_while_false_never_runs() {
+        let code = "while (false) { print 1; } print 2;";
+        assert_eq!(run_source(code), vec!["2"]);
+    }
+
+    #[test]
+    fn test_conditional_assignment() {
+        // `missing()` is undefined, so evaluating a skipped right side would fail.
+        let code = "let mut a = nil; a ??= 5; let mut b = false; b ??= missing(); \
+                    let mut c = false; c ||= 6; let d = 7; d ||= missing(); print [a, b, c, d];";
+        assert_eq!(run_source(code), vec!["[5, false, 6, 7]"]);
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = "let mut x = 10; x -= 3; print x; x *= 2; print x; x += 1; x /= 5; print x;";
+        interpreter.run(program(code)).unwrap();
+        assert_eq!(printed.lines(), vec!["7", "14", "3.0"]);
+        // The target must be mutable, as with plain assignment.
+        assert!(interpreter.run(program("let y = 1; y += 1;")).is_err());
+    }
+
+    #[test]
+    fn test_declaration_without_initializer() {
+        let code = "let x; print x; let mut y; y = 3; print y;";
+        assert_eq!(run_source(code), vec!["nil", "3"]);
+    }
+
+    #[test]
+    fn test_conditional_expression() {
+        // Only the taken branch runs, so `missing()` is never called.
+        let code = "print true ? 1 : 2; let x = nil ? missing() : \"else\"; print x; print 0 ? 1 : 2 ? 3 : 4;";
+        assert_eq!(run_source(code), vec!["1", "else", "1"]);
+    }
+
+    #[test]
+    fn test_for_loop() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = "let mut sum = 0; for (let i = 1; i <= 5; i = i + 1) { sum = sum + i; } print sum;";
+        interpreter.run(program(code)).unwrap();
+        assert_eq!(printed.lines(), vec!["15"]);
+
+        // The loop variable does not leak out of the loop.
+        let code = "for (let j = 0; j < 1; j = j + 1) {} print j;";
+        assert!(interpreter.run(program(code)).is_err());
+    }
+
+    #[test]
+    fn test_for_in_loop() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = "let mut sum = 0; for (x in [1, 2, 3, 4]) { sum = sum + x; } print sum;
+                    for (x in [1, 2, 3, 4, 5]) { if (x == 2) continue; if (x == 4) break; print x; }";
+        interpreter.run(program(code)).unwrap();
+        assert_eq!(printed.lines(), vec!["10", "1", "3"]);
+
+        printed.clear();
+        let code = "for (c in \"héllo\") print c;";
+        interpreter.run(program(code)).unwrap();
+        assert_eq!(printed.lines().concat(), "héllo");
+        assert_eq!(printed.lines().len(), 5);
+
+        // Each iteration gets its own binding, which a closure keeps.
+        printed.clear();
+        let code = "let mut first = nil; for (x in [1, 2]) { if (x == 1) first = fn() { return x; }; } print first();";
+        interpreter.run(program(code)).unwrap();
+        assert_eq!(printed.lines(), vec!["1"]);
+
+        let code = "\nfor (x in 5) print x;";
+        let result = interpreter.interpret(program(code));
+        assert_eq!(result.unwrap_err(), "cannot iterate over integer at line 2");
+        let code = "for (x in []) {} print x;";
+        assert!(interpreter.run(program(code)).is_err());
+    }
+
+    #[test]
+    fn test_logical_operators_short_circuit() {
+        // `undefined_var` would raise an error if it were evaluated.
+        let code = "print true or undefined_var; print false and undefined_var; \
+                    print nil or \"fallback\"; print 1 and 2; print nil and 1; print false or nil;";
+        assert_eq!(run_source(code), vec!["true", "false", "fallback", "2", "nil", "nil"]);
+    }
+
+    #[test]
+    fn test_nil_propagation() {
+        let run = |interpreter: &mut Interpreter, code: &str| {
+            interpreter.run(program(code))
+        };
+        assert!(run(&mut Interpreter::new(), "nil + 1;").is_err());
+
+        let mut interpreter = Interpreter::new().with_nil_propagation(true);
+        assert_eq!(run(&mut interpreter, "nil + 1;").unwrap(), Value::Nil);
+        assert_eq!(run(&mut interpreter, "-nil * 2 < 3;").unwrap(), Value::Nil);
+        assert_eq!(run(&mut interpreter, "nil == nil;").unwrap(), Value::Bool(true));
+        assert!(run(&mut interpreter, "\"a\" - 1;").is_err());
+    }
+
+    #[test]
+    fn test_sprint_uses_formatter() {
+        let mut interpreter = Interpreter::new().with_formatter(|value| format!("<{}>", value.stringify()));
+        let statements = program("sprint(1, \"x\");");
+        assert_eq!(interpreter.run(statements).unwrap(), Value::string("<1> <x>"));
+    }
+
+    #[test]
+    fn test_assignment() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = "let mut x = 1; x = 2; print x; { x = 3; } print x; let mut y = 0; print y = x + 1; print y;";
+        interpreter.run(program(code)).unwrap();
+        assert_eq!(printed.lines(), vec!["2", "3", "4", "4"]);
+
+        let result = interpreter.interpret(program("let z = 1; z = 2;"));
+        assert!(result.unwrap_err().contains("immutable variable 'z'"));
+        assert!(interpreter.run(program("undeclared = 1;")).is_err());
+        assert!(parse_stmt(scan_tokens("(x) = 1;".to_string()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = "fn size(s) { return byte_len(s); } print sort_by([\"ccc\", \"a\", \"bb\", \"d\"], size);";
+        interpreter.run(program(code)).unwrap();
+        assert_eq!(printed.lines(), vec!["[\"a\", \"d\", \"bb\", \"ccc\"]"]);
+
+        let code = "fn id(x) { return x; } sort_by([1, \"a\"], id);";
+        let result = interpreter.interpret(program(code));
+        assert!(result.unwrap_err().contains("can't compare keys"));
+    }
+
+    #[test]
+    fn test_find() {
+        let code = "fn big(x) { return x > 2; } print find([1, 3, 5], big); print find([1, 2], big); print find([], big);";
+        assert_eq!(run_source(code), vec!["3", "nil", "nil"]);
+    }
+
+    #[test]
+    fn test_filter_map_drops_nil() {
+        let code = "fn scale_big(x) { if (x > 2) { return x * 10; } }
+            fn is_one(x) { return x == 1; }
+            print filter_map([1, 2, 3, 4], scale_big);
+            print filter_map([1, 2], is_one);";
+        // Only nil is dropped; `false` is a result like any other.
+        assert_eq!(run_source(code), vec!["[30, 40]", "[true, false]"]);
+    }
+
+    #[test]
+    fn test_block_assignment_reaches_outer_variable() {
+        let code = "let mut x = 1; { x = 2; } print x; { let x = 3; print x; } print x;";
+        assert_eq!(run_source(code), vec!["2", "3", "2"]);
+    }
+
+    #[test]
+    fn test_anonymous_function() {
+        let code = "let add = fn(a,b){ return a+b; }; print add(2,3); \
+                    fn twice(f, x) { return f(f(x)); } print twice(fn(n) { return n * 10; }, 4);";
+        assert_eq!(run_source(code), vec!["5", "400"]);
+    }
+
+    #[test]
+    fn test_counter_closure() {
+        let code = "fn make_counter() { let mut n = 0; fn next() { n = n + 1; return n; } return next; } \
+                    let a = make_counter(); let b = make_counter(); a(); a(); print a(); print b();";
+        assert_eq!(run_source(code), vec!["3", "1"]);
+    }
+
+    #[test]
+    fn test_closure_keeps_the_binding_it_resolved() {
+        let code = "let a = \"global\"; { fn show() { print a; } show(); let a = \"block\"; show(); print a; }";
+        assert_eq!(run_source(code), vec!["global", "global", "block"]);
+    }
+
+    #[test]
+    fn test_mutually_recursive_local_functions() {
+        let code = "{ fn is_even(n) { if (n == 0) return true; return is_odd(n - 1); } \
+                    fn is_odd(n) { if (n == 0) return false; return is_even(n - 1); } print is_even(10); }";
+        assert_eq!(run_source(code), vec!["true"]);
+    }
+
+    #[test]
+    fn test_functions_do_not_see_caller_locals() {
+        let mut interpreter = Interpreter::new();
+        let code = "fn peek() { return secret; } fn caller() { let secret = 1; return peek(); } caller();";
+        let result = interpreter.interpret(program(code));
+        assert!(result.unwrap_err().contains("Undefined variable 'secret'"));
+    }
+
+    #[test]
+    fn test_continue_runs_for_increment() {
+        let code = "for (let i = 0; i < 5; i = i + 1) { if (i == 1 or i == 3) continue; print i; }";
+        assert_eq!(run_source(code), vec!["0", "2", "4"]);
+    }
+
+    #[test]
+    fn test_break_exits_innermost_loop() {
+        let code = "let mut i = 0; while (true) { i = i + 1; for (;;) break; if (i == 3) break; } print i;";
+        assert_eq!(run_source(code), vec!["3"]);
+    }
+
+    #[test]
+    fn test_break_and_continue_in_while() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = "let mut i = 0; while (i < 10) { if (i == 3) break; print i; i = i + 1; }";
+        interpreter.run(program(code)).unwrap();
+        assert_eq!(printed.lines(), vec!["0", "1", "2"]);
+
+        printed.clear();
+        let code = "let mut j = 0; while (j < 6) { j = j + 1; if (j == 2 or j == 4 or j == 6) continue; print j; }";
+        interpreter.run(program(code)).unwrap();
+        assert_eq!(printed.lines(), vec!["1", "3", "5"]);
+    }
+
+    #[test]
+    fn test_dbg_pretty_writes_to_error_sink() {
+        let sink = Rc::new(std::cell::RefCell::new(Vec::<u8>::new()));
+        let mut interpreter = Interpreter::new().with_error_sink(sink.clone());
+        let statements = program("dbg_pretty([1, {\"a\": 2}]);");
+        let value = interpreter.run(statements).unwrap();
+        assert_eq!(value.stringify(), "[1, {\"a\": 2}]");
+        let output = String::from_utf8(sink.borrow().clone()).unwrap();
+        assert_eq!(output, "[\n  1,\n  {\n    \"a\": 2\n  }\n]\n");
+    }
+
+    #[test]
+    fn test_recursive_function() {
+        let code = "fn fib(n) { if (n < 2) { return n; } return fib(n-1) + fib(n-2); } print fib(10);";
+        assert_eq!(run_source(code), vec!["55"]);
+    }
+
+    #[test]
+    fn test_infinite_recursion_is_an_error() {
+        // Test threads have small stacks; give the default limit the room the
+        // `spade` binary gives it.
+        let result = std::thread::Builder::new().stack_size(16 * 1024 * 1024).spawn(|| {
+            let mut interpreter = Interpreter::new();
+            let code = "fn forever(n) {\n  return forever(n + 1);\n}\nforever(0);";
+            // Values can't leave the thread.
+            interpreter.interpret(program(code)).map(|_| ())
+        }).unwrap().join().unwrap();
+        assert_eq!(result.unwrap_err(), "stack overflow: more than 1000 nested calls at line 2");
+    }
+
+    #[test]
+    fn test_max_call_depth() {
+        let mut interpreter = Interpreter::new().with_max_call_depth(5);
+        let code = "fn depth(n) { if (n == 1) return 1; return 1 + depth(n - 1); }";
+        interpreter.run(program(code)).unwrap();
+        let depth = |interpreter: &mut Interpreter, n: usize| {
+            interpreter.run(program(&format!("depth({});", n)))
+        };
+        assert!(matches!(depth(&mut interpreter, 5).unwrap(), Value::Integer(5)));
+        assert!(matches!(depth(&mut interpreter, 6), Err(SpadeError::RuntimeError { .. })));
+        // The failed call unwound completely.
+        assert!(matches!(depth(&mut interpreter, 5).unwrap(), Value::Integer(5)));
+    }
+
+    #[test]
+    fn test_property_access() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = "let point = {\"x\": 3, \"y\": 4}; print point.x * point.y;";
+        interpreter.run(program(code)).unwrap();
+        assert_eq!(printed.lines(), vec!["12"]);
+
+        let result = interpreter.interpret(program("point.z;"));
+        assert_eq!(result.unwrap_err(), "Map has no key 'z' (reading a missing key is an error) at line 1");
+        let result = interpreter.interpret(program("\n\"foo\".something;"));
+        assert_eq!(result.unwrap_err(), "Cannot read property 'something' of string at line 2");
+    }
+
+    #[test]
+    fn test_print_map_in_insertion_order() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = "let m = {\"zed\": 1, \"amy\": {\"k\": nil}, \"mo\": \"x\"}; print m; print m, keys(m);";
+        interpreter.run(program(code)).unwrap();
+        let map = "{\"zed\": 1, \"amy\": {\"k\": nil}, \"mo\": \"x\"}";
+        assert_eq!(printed.lines(), vec![map.to_string(), format!("{} [\"zed\", \"amy\", \"mo\"]", map)]);
+    }
+
+    #[test]
+    fn test_map_indexing() {
+        let (mut interpreter, printed) = recording_interpreter();
+        let code = "let ages = {\"ann\": 31, \"bob\": 27}; let name = \"bob\"; print ages[\"ann\"]; print ages[name] == ages.bob;";
+        interpreter.run(program(code)).unwrap();
+        assert_eq!(printed.lines(), vec!["31", "true"]);
+
+        let result = interpreter.run(program("\nages[\"cy\"];"));
+        match result {
+            Err(SpadeError::RuntimeError { message, line }) => {
+                assert_eq!(message, "Map has no key 'cy' (reading a missing key is an error)");
+                assert_eq!(line, 2);
+            },
+            other => panic!("expected a missing key error, got {:?}", other),
+        }
+        let result = interpreter.interpret(program("\n\nages[0];"));
+        assert_eq!(result.unwrap_err(), "Map keys must be strings, got integer at line 3");
+    }
+
+    #[test]
+    fn test_arity_error_names_function_and_counts() {
+        let mut interpreter = Interpreter::new();
+        let code = "fn add(a, b) {\n  return a + b;\n}\nprint\n  add(1);";
+        let result = interpreter.interpret(program(code));
+        assert_eq!(result.unwrap_err(), "add() expects 2 argument(s) but got 1 at line 5");
+
+        let result = interpreter.interpret(program("let f = fn(x) { return x; };\nf(1, 2);"));
+        assert_eq!(result.unwrap_err(), "Anonymous function expects 1 argument(s) but got 2 at line 2");
+        // Native functions report the call site too.
+        let result = interpreter.interpret(program("\n\nlen();"));
+        assert_eq!(result.unwrap_err(), "len() expects 1 argument(s) but got 0 at line 3");
+    }
+
+    #[test]
+    fn test_runtime_errors_report_line() {
+        let mut interpreter = Interpreter::new();
+        let code = "let a = 1;\nlet b = \"two\";\nprint a +\n  b;";
+        let result = interpreter.interpret(program(code));
+        assert_eq!(result.unwrap_err(), "cannot apply '+' to integer and string at line 3");
+
+        let result = interpreter.interpret(program("\n\n-b;"));
+        assert_eq!(result.unwrap_err(), "cannot apply unary '-' to string at line 3");
+
+        let result = interpreter.interpret(program("\n\n\n1 / 0;"));
+        assert_eq!(result.unwrap_err(), "Division by zero at line 4");
+    }
+}
@@ -1,7 +1,18 @@
 pub mod token;
 pub mod tree;
 pub mod expressions;
+pub mod fold;
 pub mod evaluate;
 pub mod interpreter;
 pub mod environment;
-pub mod error;
\ No newline at end of file
+pub mod error;
+pub mod natives;
+pub mod resolver;
+pub mod runtime;
+pub mod source;
+pub mod spade;
+
+#[cfg(test)]
+mod test_support;
+
+pub use spade::{run, Spade};
\ No newline at end of file
@@ -1,3 +1,28 @@
+use spade::Spade;
+
+// Evaluation recurses on the native stack, so the interpreter runs on a thread
+// with room for the default call depth limit of 1000, which needs about 10 MiB
+// in unoptimized builds (see `Interpreter::with_max_call_depth`).
+const STACK_SIZE: usize = 16 * 1024 * 1024;
+
+/// `spade FILE` runs a script; with no arguments, starts a REPL that echoes
+/// the value of each expression.
 fn main() {
-    println!("Hello, world!");
+    let interpreter = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| {
+            let mut spade = Spade::new();
+            let result = match std::env::args().nth(1) {
+                Some(path) => spade.run_file(&path).map(|_| ()),
+                None => spade.repl(std::io::stdin().lock()),
+            };
+            // Errors can hold values, which can't leave the thread.
+            result.map_err(|e| e.to_string())
+        })
+        .expect("failed to start the interpreter thread");
+    let result = interpreter.join().unwrap_or_else(|_| std::process::exit(101));
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
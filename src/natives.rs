@@ -0,0 +1,903 @@
+use std::io::{self, BufRead, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::environment::Environment;
+use crate::error::SpadeError;
+use crate::evaluate::{call_value, evaluate_expression, print_format, NativeFn, NativeFunction, Value};
+use crate::token::scan_tokens;
+use crate::tree::{parse, parse_stmt};
+
+/// Registers every built-in function in the given (global) environment.
+pub fn define_natives(env: &mut Environment) {
+    define(env, "debug_ast", debug_ast);
+    define(env, "pad_left", pad_left);
+    define(env, "pad_right", pad_right);
+    define(env, "slice", slice);
+    define(env, "take", take);
+    define(env, "drop", drop);
+    define(env, "starts_with", starts_with);
+    define(env, "ends_with", ends_with);
+    define(env, "breakpoint", breakpoint);
+    define(env, "len", len);
+    define(env, "byte_len", byte_len);
+    define(env, "gcd", gcd);
+    define(env, "lcm", lcm);
+    define(env, "min", min);
+    define(env, "max", max);
+    define(env, "keys", keys);
+    define(env, "keys_sorted", keys_sorted);
+    define(env, "on_close", on_close);
+    define(env, "chunk", chunk);
+    define(env, "window", window);
+    define(env, "sprint", sprint);
+    define(env, "is_empty", is_empty);
+    define(env, "sort", sort);
+    define(env, "sort_by", sort_by);
+    define(env, "find", find);
+    define(env, "filter_map", filter_map);
+    define(env, "trim_start", trim_start);
+    define(env, "trim_end", trim_end);
+    define(env, "replace", replace);
+    define(env, "dbg_pretty", dbg_pretty);
+    define(env, "clone", clone);
+    define(env, "to_list", to_list);
+    define(env, "join", join);
+    define(env, "clock", clock);
+    define(env, "str", str);
+    define(env, "num", num);
+}
+
+fn define(env: &mut Environment, name: &str, function: NativeFunction) {
+    env.define(name.to_string(), Value::NativeFn(NativeFn::new(name, function)));
+}
+
+fn check_arity(name: &str, args: &[Value], expected: usize) -> Result<(), SpadeError> {
+    if args.len() != expected {
+        return Err(SpadeError::runtime_error(
+            format!("{}() expects {} argument(s) but got {}", name, expected, args.len()),
+            0,
+        ));
+    }
+    Ok(())
+}
+
+fn expect_string(name: &str, value: &Value) -> Result<String, SpadeError> {
+    match value {
+        Value::String(s) => Ok(s.to_string()),
+        _ => Err(SpadeError::runtime_error(format!("{}() expects a string argument", name), 0)),
+    }
+}
+
+fn expect_list(name: &str, value: &Value) -> Result<Vec<Value>, SpadeError> {
+    match value {
+        Value::List(elements) => Ok(elements.borrow().clone()),
+        _ => Err(SpadeError::runtime_error(format!("{}() expects a list argument", name), 0)),
+    }
+}
+
+fn expect_map_keys(name: &str, value: &Value) -> Result<Vec<String>, SpadeError> {
+    match value {
+        Value::Map(entries) => Ok(entries.borrow().keys().cloned().collect()),
+        _ => Err(SpadeError::runtime_error(format!("{}() expects a map argument", name), 0)),
+    }
+}
+
+fn expect_number(name: &str, value: &Value) -> Result<f64, SpadeError> {
+    match value {
+        Value::Integer(n) => Ok(*n as f64),
+        Value::Number(n) => Ok(*n),
+        _ => Err(SpadeError::runtime_error(format!("{}() expects number arguments", name), 0)),
+    }
+}
+
+fn expect_index(name: &str, value: &Value) -> Result<usize, SpadeError> {
+    match value {
+        Value::Integer(n) if *n >= 0 => Ok(*n as usize),
+        _ => Err(SpadeError::runtime_error(format!("{}() expects a non-negative integer argument", name), 0)),
+    }
+}
+
+fn expect_integer(name: &str, value: &Value) -> Result<i64, SpadeError> {
+    match value {
+        Value::Integer(n) => Ok(*n),
+        _ => Err(SpadeError::runtime_error(format!("{}() expects integer arguments", name), 0)),
+    }
+}
+
+fn expect_size(name: &str, value: &Value) -> Result<usize, SpadeError> {
+    match value {
+        Value::Integer(n) if *n >= 1 => Ok(*n as usize),
+        _ => Err(SpadeError::runtime_error(format!("{}() expects a positive integer size", name), 0)),
+    }
+}
+
+// Parses the source as statements, falling back to a single expression so
+// that `debug_ast("1+2")` works without a trailing semicolon.
+fn debug_ast(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("debug_ast", &args, 1)?;
+    let source = expect_string("debug_ast", &args[0])?;
+    let tokens = match scan_tokens(source) {
+        Ok(tokens) => tokens,
+        Err(e) => return Ok(Value::string(e.to_string())),
+    };
+    let tree = match parse_stmt(tokens.clone()) {
+        Ok(statements) => statements.iter().map(|s| s.to_string()).collect::<Vec<String>>().join(" "),
+        Err(statement_error) => match parse(tokens) {
+            Ok(expr) => expr.to_string(),
+            Err(_) => statement_error.to_string(),
+        },
+    };
+    Ok(Value::string(tree))
+}
+
+// Shared implementation of `pad_left(str, width, fill)` and
+// `pad_right(str, width, fill)`; `fill` defaults to a space.
+fn pad(name: &str, args: Vec<Value>, left: bool) -> Result<Value, SpadeError> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err(SpadeError::runtime_error(
+            format!("{}() expects 2 or 3 arguments but got {}", name, args.len()),
+            0,
+        ));
+    }
+    let string = expect_string(name, &args[0])?;
+    let width = expect_index(name, &args[1])?;
+    let fill = match args.get(2) {
+        Some(value) => {
+            let fill = expect_string(name, value)?;
+            let mut chars = fill.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(SpadeError::runtime_error(format!("{}() fill must be a single character", name), 0)),
+            }
+        },
+        None => ' ',
+    };
+    let length = string.chars().count();
+    if length >= width {
+        return Ok(Value::string(string));
+    }
+    let padding: String = std::iter::repeat_n(fill, width - length).collect();
+    if left {
+        Ok(Value::string(padding + &string))
+    } else {
+        Ok(Value::string(string + &padding))
+    }
+}
+
+fn pad_left(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    pad("pad_left", args, true)
+}
+
+fn pad_right(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    pad("pad_right", args, false)
+}
+
+// Copies `elements[start..end]` into a new list, clamping both bounds to the
+// list's length so out-of-range slices never fail.
+fn slice_list(elements: &[Value], start: usize, end: usize) -> Value {
+    let end = end.min(elements.len());
+    let start = start.min(end);
+    Value::list(elements[start..end].to_vec())
+}
+
+fn slice(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("slice", &args, 3)?;
+    let elements = expect_list("slice", &args[0])?;
+    let start = expect_index("slice", &args[1])?;
+    let end = expect_index("slice", &args[2])?;
+    Ok(slice_list(&elements, start, end))
+}
+
+fn take(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("take", &args, 2)?;
+    let elements = expect_list("take", &args[0])?;
+    let n = expect_index("take", &args[1])?;
+    Ok(slice_list(&elements, 0, n))
+}
+
+fn drop(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("drop", &args, 2)?;
+    let elements = expect_list("drop", &args[0])?;
+    let n = expect_index("drop", &args[1])?;
+    Ok(slice_list(&elements, n, elements.len()))
+}
+
+// Splits a list into consecutive sub-lists of `size`; the last may be shorter.
+fn chunk(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("chunk", &args, 2)?;
+    let elements = expect_list("chunk", &args[0])?;
+    let size = expect_size("chunk", &args[1])?;
+    Ok(Value::list(elements.chunks(size).map(|c| Value::list(c.to_vec())).collect()))
+}
+
+// Every run of `size` adjacent elements, overlapping; empty when `size`
+// exceeds the list's length.
+fn window(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("window", &args, 2)?;
+    let elements = expect_list("window", &args[0])?;
+    let size = expect_size("window", &args[1])?;
+    Ok(Value::list(elements.windows(size).map(|w| Value::list(w.to_vec())).collect()))
+}
+
+// A new list with the elements in ascending order. Elements must all be
+// comparable with each other, e.g. all numbers or all strings.
+fn sort(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("sort", &args, 1)?;
+    let elements = expect_list("sort", &args[0])?;
+    let keyed = elements.into_iter().map(|element| (element.clone(), element)).collect();
+    let sorted = sort_keyed(keyed).map_err(|(a, b)| {
+        SpadeError::runtime_error(format!("sort() can't compare values of type {} and {}", a, b), 0)
+    })?;
+    Ok(Value::list(sorted))
+}
+
+// Stable sort by the key `keyfn` computes for each element.
+fn sort_by(env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("sort_by", &args, 2)?;
+    let elements = expect_list("sort_by", &args[0])?;
+    let mut keyed = vec![];
+    for element in elements {
+        let key = call_value(args[1].clone(), vec![element.clone()], env)?;
+        keyed.push((key, element));
+    }
+    let sorted = sort_keyed(keyed).map_err(|(a, b)| {
+        SpadeError::runtime_error(format!("sort_by() can't compare keys of type {} and {}", a, b), 0)
+    })?;
+    Ok(Value::list(sorted))
+}
+
+// Stable sort of (key, element) pairs by key, failing with the types of the
+// first two keys found to be incomparable.
+fn sort_keyed(mut keyed: Vec<(Value, Value)>) -> Result<Vec<Value>, (&'static str, &'static str)> {
+    let mut incomparable = None;
+    keyed.sort_by(|(a, _), (b, _)| {
+        a.partial_cmp(b).unwrap_or_else(|| {
+            incomparable.get_or_insert_with(|| (a.type_name(), b.type_name()));
+            std::cmp::Ordering::Equal
+        })
+    });
+    match incomparable {
+        Some(types) => Err(types),
+        None => Ok(keyed.into_iter().map(|(_, element)| element).collect()),
+    }
+}
+
+// The first element the predicate accepts, or nil. Stops calling the
+// predicate once it has a match.
+fn find(env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("find", &args, 2)?;
+    let elements = expect_list("find", &args[0])?;
+    for element in elements {
+        if call_value(args[1].clone(), vec![element.clone()], env)?.is_truthy() {
+            return Ok(element);
+        }
+    }
+    Ok(Value::Nil)
+}
+
+// Maps each element and keeps the results that aren't nil.
+fn filter_map(env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("filter_map", &args, 2)?;
+    let elements = expect_list("filter_map", &args[0])?;
+    let mut results = vec![];
+    for element in elements {
+        let result = call_value(args[1].clone(), vec![element], env)?;
+        if result != Value::Nil {
+            results.push(result);
+        }
+    }
+    Ok(Value::list(results))
+}
+
+fn trim_start(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("trim_start", &args, 1)?;
+    let string = expect_string("trim_start", &args[0])?;
+    Ok(Value::string(string.trim_start().to_string()))
+}
+
+fn trim_end(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("trim_end", &args, 1)?;
+    let string = expect_string("trim_end", &args[0])?;
+    Ok(Value::string(string.trim_end().to_string()))
+}
+
+// Replaces every non-overlapping occurrence of `from`. An empty `from` would
+// match between every character, so it is rejected.
+fn replace(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("replace", &args, 3)?;
+    let string = expect_string("replace", &args[0])?;
+    let from = expect_string("replace", &args[1])?;
+    let to = expect_string("replace", &args[2])?;
+    if from.is_empty() {
+        return Err(SpadeError::runtime_error("replace() can't replace an empty string".to_string(), 0));
+    }
+    Ok(Value::string(string.replace(&from, &to)))
+}
+
+fn starts_with(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("starts_with", &args, 2)?;
+    let string = expect_string("starts_with", &args[0])?;
+    let prefix = expect_string("starts_with", &args[1])?;
+    Ok(Value::Bool(string.starts_with(&prefix)))
+}
+
+fn ends_with(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("ends_with", &args, 2)?;
+    let string = expect_string("ends_with", &args[0])?;
+    let suffix = expect_string("ends_with", &args[1])?;
+    Ok(Value::Bool(string.ends_with(&suffix)))
+}
+
+// Converts any value to the string `print` would show for it.
+fn str(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("str", &args, 1)?;
+    Ok(Value::string(args[0].stringify()))
+}
+
+// Parses a string as a number, ignoring surrounding whitespace. Digits alone
+// give an integer, anything else a float.
+fn num(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("num", &args, 1)?;
+    let string = expect_string("num", &args[0])?;
+    if let Ok(n) = string.trim().parse::<i64>() {
+        return Ok(Value::Integer(n));
+    }
+    // Rust also parses "nan" and "inf", which aren't Spade number literals.
+    match string.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() => Ok(Value::Number(n)),
+        _ => Err(SpadeError::runtime_error(format!("num() can't parse '{}' as a number", string), 0)),
+    }
+}
+
+// Strings are measured in chars (Unicode scalar values), not bytes.
+fn len(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("len", &args, 1)?;
+    match &args[0] {
+        Value::String(s) => Ok(Value::Integer(s.chars().count() as i64)),
+        Value::List(elements) => Ok(Value::Integer(elements.borrow().len() as i64)),
+        other => Err(SpadeError::runtime_error(format!("len() expects a string or list, got {}", other.type_name()), 0)),
+    }
+}
+
+fn is_empty(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("is_empty", &args, 1)?;
+    match &args[0] {
+        Value::String(s) => Ok(Value::Bool(s.is_empty())),
+        Value::List(elements) => Ok(Value::Bool(elements.borrow().is_empty())),
+        Value::Map(entries) => Ok(Value::Bool(entries.borrow().is_empty())),
+        other => Err(SpadeError::runtime_error(format!("is_empty() expects a string, list or map, got {}", other.type_name()), 0)),
+    }
+}
+
+fn byte_len(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("byte_len", &args, 1)?;
+    let string = expect_string("byte_len", &args[0])?;
+    Ok(Value::Integer(string.len() as i64))
+}
+
+// Euclid's algorithm on absolute values, so gcd(0, n) == |n|.
+fn euclid(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn gcd(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("gcd", &args, 2)?;
+    let a = expect_integer("gcd", &args[0])?;
+    let b = expect_integer("gcd", &args[1])?;
+    Ok(Value::Integer(euclid(a, b)))
+}
+
+// lcm(0, n) is 0 by convention.
+fn lcm(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("lcm", &args, 2)?;
+    let a = expect_integer("lcm", &args[0])?;
+    let b = expect_integer("lcm", &args[1])?;
+    if a == 0 || b == 0 {
+        return Ok(Value::Integer(0));
+    }
+    match (a / euclid(a, b)).checked_mul(b) {
+        Some(lcm) => Ok(Value::Integer(lcm.abs())),
+        None => Err(SpadeError::runtime_error("lcm() result is too large".to_string(), 0)),
+    }
+}
+
+// Folds one or more numbers with `pick`, which says whether the candidate
+// replaces the current extreme. NaN is contagious: if any argument is NaN
+// the result is NaN, rather than silently dropping it the way f64::min does.
+// Ties keep the earlier argument, so min(0, -0) is 0 and min(-0, 0) is -0;
+// the two compare equal either way. Infinities order like any other number.
+// The chosen argument is returned as given, so integers stay integers.
+fn extreme(name: &str, args: Vec<Value>, pick: fn(f64, f64) -> bool) -> Result<Value, SpadeError> {
+    if args.is_empty() {
+        return Err(SpadeError::runtime_error(format!("{}() expects at least one argument", name), 0));
+    }
+    let mut chosen = 0;
+    let mut result = expect_number(name, &args[0])?;
+    for (i, arg) in args.iter().enumerate().skip(1) {
+        let n = expect_number(name, arg)?;
+        if n.is_nan() || (!result.is_nan() && pick(n, result)) {
+            (chosen, result) = (i, n);
+        }
+    }
+    Ok(args[chosen].clone())
+}
+
+fn min(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    extreme("min", args, |candidate, current| candidate < current)
+}
+
+fn max(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    extreme("max", args, |candidate, current| candidate > current)
+}
+
+// A deep copy: nested lists and maps in the result are fresh collections.
+fn clone(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("clone", &args, 1)?;
+    Ok(args[0].deep_clone())
+}
+
+// Splits a string into its characters; a list (such as a range) is copied.
+fn to_list(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("to_list", &args, 1)?;
+    match &args[0] {
+        Value::String(s) => Ok(Value::list(s.chars().map(|c| Value::string(c.to_string())).collect())),
+        Value::List(elements) => Ok(Value::list(elements.borrow().clone())),
+        other => Err(SpadeError::runtime_error(format!("to_list() expects a string or list, got {}", other.type_name()), 0)),
+    }
+}
+
+fn join(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("join", &args, 2)?;
+    let elements = expect_list("join", &args[0])?;
+    let separator = expect_string("join", &args[1])?;
+    let parts = elements.iter()
+        .map(|element| expect_string("join", element))
+        .collect::<Result<Vec<String>, SpadeError>>()?;
+    Ok(Value::string(parts.join(&separator)))
+}
+
+// Returns what `print` would show for the arguments, joined by spaces.
+fn sprint(env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    let parts = args.iter().map(|value| print_format(value, env)).collect::<Vec<String>>();
+    Ok(Value::string(parts.join(" ")))
+}
+
+// Writes the multi-line rendering of a value to the error sink and returns
+// the value unchanged, so it can wrap any expression.
+fn dbg_pretty(env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("dbg_pretty", &args, 1)?;
+    let value = args.into_iter().next().unwrap();
+    let sink = env.runtime().error_sink.clone();
+    // Like tracing, debug output is best-effort.
+    let _ = writeln!(sink.borrow_mut(), "{}", value.pretty(0));
+    Ok(value)
+}
+
+// Keys in insertion order.
+fn keys(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("keys", &args, 1)?;
+    let keys = expect_map_keys("keys", &args[0])?;
+    Ok(Value::list(keys.into_iter().map(Value::string).collect()))
+}
+
+fn keys_sorted(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("keys_sorted", &args, 1)?;
+    let mut keys = expect_map_keys("keys_sorted", &args[0])?;
+    keys.sort();
+    Ok(Value::list(keys.into_iter().map(Value::string).collect()))
+}
+
+// Registers `callback` to be called with `resource` when a `with` statement
+// over it finishes.
+fn on_close(env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("on_close", &args, 2)?;
+    if !matches!(args[1], Value::Function(_) | Value::NativeFn(_)) {
+        return Err(SpadeError::runtime_error("on_close() expects a function as its second argument".to_string(), 0));
+    }
+    let mut args = args.into_iter();
+    let resource = args.next().unwrap();
+    let callback = args.next().unwrap();
+    env.runtime_mut().cleanups.push((resource.clone(), callback));
+    Ok(resource)
+}
+
+// Seconds since the Unix epoch, with sub-second precision, for timing code.
+fn clock(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("clock", &args, 0)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| SpadeError::runtime_error(format!("clock() failed: {}", e), 0))?;
+    Ok(Value::Number(now.as_secs_f64()))
+}
+
+// Pauses in the interactive debugger when debug mode is on; otherwise a no-op.
+fn breakpoint(env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+    check_arity("breakpoint", &args, 0)?;
+    if env.runtime().debug {
+        let stdin = io::stdin();
+        debug_repl(env, &mut stdin.lock(), &mut io::stdout())
+            .map_err(|e| SpadeError::runtime_error(format!("breakpoint() failed: {}", e), 0))?;
+    }
+    Ok(Value::Nil)
+}
+
+// Reads debugger commands until `continue` (or end of input). `env` dumps the
+// live scope; anything else is evaluated as an expression in that scope.
+fn debug_repl(env: &mut Environment, input: &mut dyn BufRead, output: &mut dyn Write) -> io::Result<()> {
+    writeln!(output, "breakpoint: type an expression, 'env', or 'continue'")?;
+    loop {
+        write!(output, "debug> ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        match line.trim() {
+            "" => continue,
+            "continue" | "c" => return Ok(()),
+            "env" => writeln!(output, "{}", env.dump_env())?,
+            source => {
+                let result = scan_tokens(source.to_string())
+                    .and_then(parse)
+                    .and_then(|expr| evaluate_expression(&expr, env));
+                match result {
+                    Ok(value) => writeln!(output, "{}", value.stringify())?,
+                    Err(e) => writeln!(output, "error: {}", e)?,
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: Vec<Value>) -> Result<Value, SpadeError> {
+        let mut env = Environment::new();
+        define_natives(&mut env);
+        match env.get(name).unwrap() {
+            Value::NativeFn(native) => (native.function)(&mut env, args),
+            _ => panic!("{} is not a native function", name),
+        }
+    }
+
+    #[test]
+    fn test_debug_ast_expression() {
+        let result = call("debug_ast", vec![Value::string("1+2")]).unwrap();
+        assert_eq!(result, Value::string("(1 + 2)"));
+    }
+
+    #[test]
+    fn test_debug_ast_statements() {
+        let result = call("debug_ast", vec![Value::string("let x = 1; print x;")]).unwrap();
+        assert_eq!(result, Value::string("(var x 1) (print getvar x)"));
+    }
+
+    #[test]
+    fn test_debug_ast_parse_error() {
+        let result = call("debug_ast", vec![Value::string("(1 +")]).unwrap();
+        assert!(matches!(result, Value::String(ref s) if s.starts_with("Expect")));
+        // Trailing tokens aren't silently dropped.
+        let result = call("debug_ast", vec![Value::string("1+2 3")]).unwrap();
+        assert!(matches!(result, Value::String(ref s) if s.starts_with("Expect")), "{:?}", result);
+    }
+
+    #[test]
+    fn test_debug_ast_requires_string() {
+        assert!(call("debug_ast", vec![Value::Number(1.0)]).is_err());
+        assert!(call("debug_ast", vec![]).is_err());
+    }
+
+    fn string(s: &str) -> Value {
+        Value::string(s)
+    }
+
+    #[test]
+    fn test_pad_shorter_strings() {
+        assert_eq!(call("pad_left", vec![string("7"), Value::Integer(3), string("0")]).unwrap(), string("007"));
+        assert_eq!(call("pad_right", vec![string("ab"), Value::Integer(4), string(".")]).unwrap(), string("ab.."));
+        assert_eq!(call("pad_left", vec![string("ab"), Value::Integer(4)]).unwrap(), string("  ab"));
+    }
+
+    #[test]
+    fn test_pad_longer_strings_untouched() {
+        assert_eq!(call("pad_left", vec![string("hello"), Value::Integer(3)]).unwrap(), string("hello"));
+        assert_eq!(call("pad_right", vec![string("hello"), Value::Integer(5), string("*")]).unwrap(), string("hello"));
+    }
+
+    #[test]
+    fn test_pad_invalid_fill() {
+        assert!(call("pad_left", vec![string("a"), Value::Integer(3), string("ab")]).is_err());
+        assert!(call("pad_right", vec![string("a"), Value::Integer(3), string("")]).is_err());
+        assert!(call("pad_right", vec![string("a"), Value::Integer(-1)]).is_err());
+    }
+
+    fn numbers(ns: &[f64]) -> Value {
+        Value::list(ns.iter().map(|n| Value::Number(*n)).collect())
+    }
+
+    #[test]
+    fn test_take_and_drop() {
+        let xs = numbers(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(call("take", vec![xs.clone(), Value::Integer(2)]).unwrap(), numbers(&[1.0, 2.0]));
+        assert_eq!(call("drop", vec![xs.clone(), Value::Integer(1)]).unwrap(), numbers(&[2.0, 3.0, 4.0]));
+        assert_eq!(call("slice", vec![xs, Value::Integer(1), Value::Integer(3)]).unwrap(), numbers(&[2.0, 3.0]));
+    }
+
+    #[test]
+    fn test_take_and_drop_clamped() {
+        let xs = numbers(&[1.0, 2.0]);
+        assert_eq!(call("take", vec![xs.clone(), Value::Integer(5)]).unwrap(), numbers(&[1.0, 2.0]));
+        assert_eq!(call("drop", vec![xs.clone(), Value::Integer(5)]).unwrap(), numbers(&[]));
+        assert_eq!(call("slice", vec![xs, Value::Integer(3), Value::Integer(1)]).unwrap(), numbers(&[]));
+    }
+
+    #[test]
+    fn test_take_negative_count() {
+        let xs = numbers(&[1.0, 2.0]);
+        assert!(call("take", vec![xs.clone(), Value::Integer(-1)]).is_err());
+        assert!(call("drop", vec![xs, Value::Integer(-2)]).is_err());
+    }
+
+    #[test]
+    fn test_trim_start_and_end() {
+        assert_eq!(call("trim_start", vec![string("  a b  ")]).unwrap(), string("a b  "));
+        assert_eq!(call("trim_end", vec![string("  a b \n")]).unwrap(), string("  a b"));
+        assert!(call("trim_start", vec![Value::Nil]).is_err());
+    }
+
+    #[test]
+    fn test_replace() {
+        let replace = |s: &str, from: &str, to: &str| call("replace", vec![string(s), string(from), string(to)]);
+        assert_eq!(replace("a-b-c", "-", "+").unwrap(), string("a+b+c"));
+        assert_eq!(replace("aaaa", "aa", "b").unwrap(), string("bb"));
+        assert_eq!(replace("spade", "x", "y").unwrap(), string("spade"));
+        assert!(replace("spade", "", "y").is_err());
+        assert!(call("replace", vec![string("a"), string("a"), Value::Nil]).is_err());
+    }
+
+    #[test]
+    fn test_starts_with() {
+        assert_eq!(call("starts_with", vec![string("spade"), string("spa")]).unwrap(), Value::Bool(true));
+        assert_eq!(call("starts_with", vec![string("spade"), string("ade")]).unwrap(), Value::Bool(false));
+        assert_eq!(call("starts_with", vec![string("spade"), string("")]).unwrap(), Value::Bool(true));
+        assert!(call("starts_with", vec![string("spade"), Value::Number(1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_ends_with() {
+        assert_eq!(call("ends_with", vec![string("spade"), string("ade")]).unwrap(), Value::Bool(true));
+        assert_eq!(call("ends_with", vec![string("spade"), string("spa")]).unwrap(), Value::Bool(false));
+        assert_eq!(call("ends_with", vec![string(""), string("")]).unwrap(), Value::Bool(true));
+        assert!(call("ends_with", vec![Value::Nil, string("")]).is_err());
+    }
+
+    #[test]
+    fn test_breakpoint_is_noop_without_debug() {
+        assert_eq!(call("breakpoint", vec![]).unwrap(), Value::Nil);
+    }
+
+    #[test]
+    fn test_debug_repl() {
+        let mut env = Environment::new();
+        env.define("x".to_string(), Value::Integer(20));
+        let mut input = io::Cursor::new("env\nx + 1\ncontinue\nx\n");
+        let mut output = vec![];
+        debug_repl(&mut env, &mut input, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("[0] let x = 20\n"));
+        assert!(output.contains("debug> 21\n"));
+        assert_eq!(output.matches("debug> ").count(), 3);
+    }
+
+    #[test]
+    fn test_len_counts_chars() {
+        assert!(matches!(call("len", vec![string("héllo")]).unwrap(), Value::Integer(5)));
+        assert!(matches!(call("len", vec![string("")]).unwrap(), Value::Integer(0)));
+        assert!(matches!(call("len", vec![numbers(&[1.0, 2.0])]).unwrap(), Value::Integer(2)));
+    }
+
+    #[test]
+    fn test_len_rejects_other_values() {
+        match call("len", vec![Value::Number(42.0)]) {
+            Err(SpadeError::RuntimeError { message, .. }) => assert_eq!(message, "len() expects a string or list, got number"),
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+        assert!(call("len", vec![]).is_err());
+    }
+
+    #[test]
+    fn test_clock() {
+        let first = number(call("clock", vec![]));
+        let second = number(call("clock", vec![]));
+        assert!(first > 0.0 && second >= first);
+        assert!(call("clock", vec![Value::Number(1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_str_and_num() {
+        assert_eq!(call("str", vec![Value::Integer(42)]).unwrap(), string("42"));
+        assert_eq!(call("str", vec![Value::Number(42.0)]).unwrap(), string("42.0"));
+        assert_eq!(call("str", vec![Value::Bool(true)]).unwrap(), string("true"));
+        assert_eq!(call("str", vec![numbers(&[1.0, 2.5])]).unwrap(), string("[1.0, 2.5]"));
+        assert_eq!(call("num", vec![string("3.5")]).unwrap(), Value::Number(3.5));
+        assert!(matches!(call("num", vec![string(" -2 ")]).unwrap(), Value::Integer(-2)));
+        assert!(matches!(call("num", vec![string("2.0")]).unwrap(), Value::Number(_)));
+        assert!(call("num", vec![string("abc")]).is_err());
+        for text in ["nan", "NaN", "inf", "-infinity", "1e999"] {
+            assert!(call("num", vec![string(text)]).is_err(), "{}", text);
+        }
+        assert!(call("num", vec![Value::Number(1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_byte_len() {
+        assert!(matches!(call("byte_len", vec![string("héllo")]).unwrap(), Value::Integer(6)));
+        assert!(call("byte_len", vec![numbers(&[])]).is_err());
+    }
+
+    #[test]
+    fn test_gcd() {
+        assert!(matches!(call("gcd", vec![Value::Integer(12), Value::Integer(18)]).unwrap(), Value::Integer(6)));
+        assert!(matches!(call("gcd", vec![Value::Integer(0), Value::Integer(7)]).unwrap(), Value::Integer(7)));
+        assert!(matches!(call("gcd", vec![Value::Integer(9), Value::Integer(28)]).unwrap(), Value::Integer(1)));
+        assert!(call("gcd", vec![Value::Number(1.5), Value::Integer(3)]).is_err());
+    }
+
+    #[test]
+    fn test_lcm() {
+        assert!(matches!(call("lcm", vec![Value::Integer(4), Value::Integer(6)]).unwrap(), Value::Integer(12)));
+        assert!(matches!(call("lcm", vec![Value::Integer(9), Value::Integer(28)]).unwrap(), Value::Integer(252)));
+        assert!(matches!(call("lcm", vec![Value::Integer(0), Value::Integer(5)]).unwrap(), Value::Integer(0)));
+        assert!(call("lcm", vec![string("4"), Value::Integer(6)]).is_err());
+    }
+
+    fn number(result: Result<Value, SpadeError>) -> f64 {
+        match result.unwrap() {
+            Value::Number(n) => n,
+            other => panic!("expected a number, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_min_max() {
+        let args = vec![Value::Number(3.0), Value::Number(-1.0), Value::Number(2.0)];
+        assert!(matches!(call("min", args.clone()).unwrap(), Value::Number(n) if n == -1.0));
+        assert!(matches!(call("max", args).unwrap(), Value::Number(n) if n == 3.0));
+        assert!(matches!(call("max", vec![Value::Number(5.0)]).unwrap(), Value::Number(n) if n == 5.0));
+        assert!(matches!(call("min", vec![Value::Number(2.5), Value::Integer(1)]).unwrap(), Value::Integer(1)));
+        assert!(call("min", vec![]).is_err());
+        assert!(call("max", vec![Value::Number(1.0), string("2")]).is_err());
+    }
+
+    #[test]
+    fn test_min_max_nan_propagates() {
+        for name in ["min", "max"] {
+            assert!(number(call(name, vec![Value::Number(f64::NAN), Value::Number(1.0)])).is_nan());
+            assert!(number(call(name, vec![Value::Number(1.0), Value::Number(f64::NAN)])).is_nan());
+            let trailing = vec![Value::Number(1.0), Value::Number(f64::NAN), Value::Number(2.0)];
+            assert!(number(call(name, trailing)).is_nan());
+        }
+    }
+
+    #[test]
+    fn test_min_max_signed_zero() {
+        // The earlier argument wins a tie, and -0 == 0 either way.
+        let zero = number(call("min", vec![Value::Number(0.0), Value::Number(-0.0)]));
+        assert!(zero == 0.0 && zero.is_sign_positive());
+        let zero = number(call("max", vec![Value::Number(-0.0), Value::Number(0.0)]));
+        assert!(zero == 0.0 && zero.is_sign_negative());
+    }
+
+    #[test]
+    fn test_min_max_infinity() {
+        let args = vec![Value::Number(f64::INFINITY), Value::Number(0.0), Value::Number(f64::NEG_INFINITY)];
+        assert_eq!(call("min", args.clone()).unwrap(), Value::Number(f64::NEG_INFINITY));
+        assert_eq!(call("max", args).unwrap(), Value::Number(f64::INFINITY));
+        assert_eq!(call("min", vec![Value::Number(f64::INFINITY)]).unwrap(), Value::Number(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_clone_is_deep() {
+        let inner = Value::list(vec![Value::Number(1.0)]);
+        let original = Value::list(vec![inner.clone()]);
+        let copy = call("clone", vec![original.clone()]).unwrap();
+        assert_eq!(copy, original);
+        let Value::List(outer) = &copy else { panic!("expected a list") };
+        let Value::List(nested) = &outer.borrow()[0] else { panic!("expected a list") };
+        nested.borrow_mut().push(Value::Number(2.0));
+        assert_eq!(inner, Value::list(vec![Value::Number(1.0)]));
+        assert!(call("clone", vec![]).is_err());
+    }
+
+    #[test]
+    fn test_to_list_and_join() {
+        assert_eq!(call("to_list", vec![string("abc")]).unwrap(), strings(&["a", "b", "c"]));
+        assert_eq!(call("to_list", vec![string("")]).unwrap(), strings(&[]));
+        assert_eq!(call("join", vec![strings(&["a", "b", "c"]), string("")]).unwrap(), string("abc"));
+        assert_eq!(call("join", vec![strings(&["x", "y"]), string(", ")]).unwrap(), string("x, y"));
+        assert!(call("to_list", vec![Value::Number(3.0)]).is_err());
+        assert!(call("join", vec![Value::list(vec![Value::Number(1.0)]), string("")]).is_err());
+    }
+
+    fn strings(ss: &[&str]) -> Value {
+        Value::list(ss.iter().map(|s| string(s)).collect())
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut entries = indexmap::IndexMap::new();
+        entries.insert("b".to_string(), Value::Number(1.0));
+        entries.insert("a".to_string(), Value::Number(2.0));
+        entries.insert("c".to_string(), Value::Number(3.0));
+        let map = Value::map(entries);
+        assert_eq!(call("keys", vec![map.clone()]).unwrap(), strings(&["b", "a", "c"]));
+        assert_eq!(call("keys_sorted", vec![map]).unwrap(), strings(&["a", "b", "c"]));
+        assert!(call("keys_sorted", vec![numbers(&[])]).is_err());
+    }
+
+    #[test]
+    fn test_sort() {
+        let xs = numbers(&[3.0, 1.0, 2.0]);
+        assert_eq!(call("sort", vec![xs.clone()]).unwrap(), numbers(&[1.0, 2.0, 3.0]));
+        // The argument is left as it was.
+        assert_eq!(xs, numbers(&[3.0, 1.0, 2.0]));
+        assert_eq!(call("sort", vec![strings(&["pear", "apple", "fig"])]).unwrap(), strings(&["apple", "fig", "pear"]));
+        match call("sort", vec![Value::list(vec![Value::Number(1.0), string("a")])]) {
+            Err(SpadeError::RuntimeError { message, .. }) => {
+                assert!(message.starts_with("sort() can't compare values of type"), "{}", message)
+            },
+            other => panic!("expected a comparison error, got {:?}", other),
+        }
+        assert!(call("sort", vec![string("cba")]).is_err());
+    }
+
+    fn lists(groups: &[&[f64]]) -> Value {
+        Value::list(groups.iter().map(|group| numbers(group)).collect())
+    }
+
+    #[test]
+    fn test_chunk() {
+        let chunk = |xs: &[f64], size: i64| call("chunk", vec![numbers(xs), Value::Integer(size)]);
+        assert_eq!(chunk(&[1.0, 2.0, 3.0, 4.0], 2).unwrap(), lists(&[&[1.0, 2.0], &[3.0, 4.0]]));
+        assert_eq!(chunk(&[1.0, 2.0, 3.0], 2).unwrap(), lists(&[&[1.0, 2.0], &[3.0]]));
+        assert_eq!(chunk(&[], 2).unwrap(), lists(&[]));
+        assert!(chunk(&[1.0], 0).is_err());
+        assert!(call("chunk", vec![numbers(&[1.0]), Value::Number(1.5)]).is_err());
+        assert!(call("chunk", vec![numbers(&[1.0]), Value::Number(1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_window() {
+        let window = |xs: &[f64], size: i64| call("window", vec![numbers(xs), Value::Integer(size)]);
+        assert_eq!(window(&[1.0, 2.0, 3.0], 2).unwrap(), lists(&[&[1.0, 2.0], &[2.0, 3.0]]));
+        assert_eq!(window(&[1.0, 2.0], 3).unwrap(), lists(&[]));
+        assert!(window(&[1.0], -1).is_err());
+    }
+
+    #[test]
+    fn test_sprint() {
+        let printed = call("sprint", vec![Value::Integer(1), string("x"), Value::Bool(true)]).unwrap();
+        assert_eq!(printed, string("1 x true"));
+        assert_eq!(call("sprint", vec![]).unwrap(), string(""));
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let is_empty = |value: Value| call("is_empty", vec![value]);
+        assert_eq!(is_empty(string("")).unwrap(), Value::Bool(true));
+        assert_eq!(is_empty(string("a")).unwrap(), Value::Bool(false));
+        assert_eq!(is_empty(numbers(&[])).unwrap(), Value::Bool(true));
+        assert_eq!(is_empty(numbers(&[1.0])).unwrap(), Value::Bool(false));
+        let mut entries = indexmap::IndexMap::new();
+        assert_eq!(is_empty(Value::map(entries.clone())).unwrap(), Value::Bool(true));
+        entries.insert("k".to_string(), Value::Nil);
+        assert_eq!(is_empty(Value::map(entries)).unwrap(), Value::Bool(false));
+        assert!(is_empty(Value::Number(0.0)).is_err());
+    }
+}
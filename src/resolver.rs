@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::error::SpadeError;
+use crate::expressions::{Expr, Literal, Resolution, Statement};
+use crate::token::Token;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// Declared later in the scope by `let`.
+    PendingVar,
+    /// Declared later in the scope by `fn`.
+    PendingFn,
+    /// Being declared by `let`; its initializer is being resolved.
+    Initializing,
+    Declared,
+}
+
+#[derive(Default)]
+struct Scope {
+    names: HashMap<String, State>,
+    /// The parameter scope of a function body.
+    function: bool,
+}
+
+/// Binds every variable reference and assignment to the scope that declares
+/// it, mirroring the scopes the evaluator creates: one per block, call,
+/// `with`, `let ... in` and function parameter list. The global scope isn't
+/// tracked, so anything not declared locally resolves to `Global`.
+///
+/// Reading or assigning a local before its `let` or `fn` in the same scope is
+/// an error. Inside a function body that runs later, a function declared
+/// further down the enclosing block is allowed, so local functions can call
+/// each other, but a later `let` is not seen: the reference keeps pointing
+/// outward, which is what the function would see if it were called before
+/// that `let` ran.
+pub fn resolve(statements: &mut [Statement]) -> Result<(), SpadeError> {
+    let mut resolver = Resolver { scopes: vec![] };
+    for statement in statements {
+        resolver.statement(statement)?;
+    }
+    Ok(())
+}
+
+struct Resolver {
+    scopes: Vec<Scope>,
+}
+
+impl Resolver {
+    fn statement(&mut self, statement: &mut Statement) -> Result<(), SpadeError> {
+        match statement {
+            Statement::Expression(expr) => self.expr(expr),
+            Statement::Print(exprs) => exprs.iter_mut().try_for_each(|expr| self.expr(expr)),
+            Statement::Block(statements) => {
+                let mut scope = Scope::default();
+                for statement in statements.iter() {
+                    let (name, state) = match statement {
+                        Statement::VarDec { name, .. } => (name, State::PendingVar),
+                        Statement::Fn { name, .. } => (name, State::PendingFn),
+                        _ => continue,
+                    };
+                    scope.names.entry(name.clone()).or_insert(state);
+                }
+                self.scopes.push(scope);
+                let result = statements.iter_mut().try_for_each(|statement| self.statement(statement));
+                self.scopes.pop();
+                result
+            },
+            Statement::VarDec { name, initializer, .. } => {
+                if let Some(scope) = self.scopes.last_mut() {
+                    let state = scope.names.entry(name.clone()).or_insert(State::Initializing);
+                    if *state != State::Declared {
+                        *state = State::Initializing;
+                    }
+                }
+                if let Some(initializer) = initializer {
+                    self.expr(initializer)?;
+                }
+                self.declare(name);
+                Ok(())
+            },
+            Statement::If { condition, then_branch, else_branch } => {
+                self.expr(condition)?;
+                self.statement(then_branch)?;
+                match else_branch {
+                    Some(else_branch) => self.statement(else_branch),
+                    None => Ok(()),
+                }
+            },
+            Statement::Fn { name, parameters, body } => {
+                // Declared first so the body can call itself.
+                self.declare(name);
+                self.function(parameters, Rc::make_mut(body))
+            },
+            Statement::Return(expr) => match expr {
+                Some(expr) => self.expr(expr),
+                None => Ok(()),
+            },
+            Statement::While { condition, body, increment } => {
+                self.expr(condition)?;
+                self.statement(body)?;
+                match increment {
+                    Some(increment) => self.expr(increment),
+                    None => Ok(()),
+                }
+            },
+            Statement::ForIn { name, iterable, body, .. } => {
+                self.expr(iterable)?;
+                self.scoped(std::slice::from_ref(name), false, |resolver| resolver.statement(body))
+            },
+            Statement::Break | Statement::Continue | Statement::Import { .. } => Ok(()),
+            Statement::Match { subject, arms } => {
+                self.expr(subject)?;
+                for arm in arms {
+                    if let Some(pattern) = &mut arm.pattern {
+                        self.expr(pattern)?;
+                    }
+                    if let Some(guard) = &mut arm.guard {
+                        self.expr(guard)?;
+                    }
+                    self.statement(&mut arm.body)?;
+                }
+                Ok(())
+            },
+            Statement::With { name, resource, body } => {
+                self.expr(resource)?;
+                self.scoped(std::slice::from_ref(name), false, |resolver| resolver.statement(body))
+            },
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr) -> Result<(), SpadeError> {
+        match expr {
+            Expr::Binary { left, right, .. } => {
+                self.expr(left)?;
+                self.expr(right)
+            },
+            Expr::Unary { expr, .. } | Expr::Grouping(expr) | Expr::Spread(expr) | Expr::Get { object: expr, .. } => {
+                self.expr(expr)
+            },
+            Expr::Literal(Literal::Var(token, resolution)) => {
+                *resolution = self.lookup(token)?;
+                Ok(())
+            },
+            Expr::Literal(_) => Ok(()),
+            Expr::Assign { token, value, resolution } => {
+                self.expr(value)?;
+                *resolution = self.lookup(token)?;
+                Ok(())
+            },
+            Expr::Call { callee, arguments, .. } => {
+                self.expr(callee)?;
+                arguments.iter_mut().try_for_each(|argument| self.expr(argument))
+            },
+            Expr::List(elements) | Expr::Interpolation(elements) => {
+                elements.iter_mut().try_for_each(|element| self.expr(element))
+            },
+            Expr::Index { object, index, .. } => {
+                self.expr(object)?;
+                self.expr(index)
+            },
+            Expr::Map(entries) => entries.iter_mut().try_for_each(|(key, value)| {
+                self.expr(key)?;
+                self.expr(value)
+            }),
+            Expr::Let { name, value, body } => {
+                self.expr(value)?;
+                self.scoped(std::slice::from_ref(name), false, |resolver| resolver.expr(body))
+            },
+            Expr::Conditional { condition, then_branch, else_branch } => {
+                self.expr(condition)?;
+                self.expr(then_branch)?;
+                self.expr(else_branch)
+            },
+            Expr::Function { parameters, body } => self.function(parameters, Rc::make_mut(body)),
+        }
+    }
+
+    fn function(&mut self, parameters: &[String], body: &mut Statement) -> Result<(), SpadeError> {
+        self.scoped(parameters, true, |resolver| resolver.statement(body))
+    }
+
+    // Resolves `inner` in a new scope that declares `names`.
+    fn scoped(
+        &mut self,
+        names: &[String],
+        function: bool,
+        inner: impl FnOnce(&mut Self) -> Result<(), SpadeError>,
+    ) -> Result<(), SpadeError> {
+        let names = names.iter().map(|name| (name.clone(), State::Declared)).collect();
+        self.scopes.push(Scope { names, function });
+        let result = inner(self);
+        self.scopes.pop();
+        result
+    }
+
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.names.insert(name.to_string(), State::Declared);
+        }
+    }
+
+    fn lookup(&self, token: &Token) -> Result<Resolution, SpadeError> {
+        let name = &token.lexeme;
+        // Set once the search has left the function the reference is in.
+        let mut deferred = false;
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            match (scope.names.get(name), deferred) {
+                (Some(State::Declared), _) | (Some(State::Initializing | State::PendingFn), true) => {
+                    return Ok(Resolution::Local(depth));
+                },
+                (Some(State::Initializing), false) => {
+                    return Err(error(token, format!("Cannot read local variable '{}' in its own initializer", name)));
+                },
+                (Some(State::PendingVar | State::PendingFn), false) => {
+                    return Err(error(token, format!("Local variable '{}' is used before its declaration", name)));
+                },
+                (Some(State::PendingVar), true) | (None, _) => {},
+            }
+            deferred |= scope.function;
+        }
+        Ok(Resolution::Global)
+    }
+}
+
+fn error(token: &Token, message: String) -> SpadeError {
+    SpadeError::ParseError { message, line: token.line, column: token.column }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::scan_tokens;
+    use crate::tree::parse_stmt;
+
+    fn resolved(source: &str) -> Result<Vec<Statement>, SpadeError> {
+        let mut statements = parse_stmt(scan_tokens(source.to_string()).unwrap()).unwrap();
+        resolve(&mut statements).map(|_| statements)
+    }
+
+    fn error_message(source: &str) -> String {
+        match resolved(source) {
+            Err(SpadeError::ParseError { message, .. }) => message,
+            other => panic!("expected a resolution error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_use_before_declaration() {
+        assert_eq!(error_message("{ print y; let y = 1; }"), "Local variable 'y' is used before its declaration");
+        assert_eq!(error_message("fn f() { y = 2; let mut y = 1; }"), "Local variable 'y' is used before its declaration");
+        assert_eq!(error_message("{ let x = x + 1; }"), "Cannot read local variable 'x' in its own initializer");
+        assert!(matches!(resolved("{\n  print y; let y = 1; }"), Err(SpadeError::ParseError { line: 2, column: 9, .. })));
+        // Globals may be defined by a later statement or an earlier REPL line.
+        assert!(resolved("print y; let y = 1;").is_ok());
+    }
+
+    #[test]
+    fn test_nested_references_resolve() {
+        let statements = resolved("fn outer(a) { let b = a; fn inner() { return a + b + c; } }").unwrap();
+        let Statement::Fn { body, .. } = &statements[0] else { panic!() };
+        let Statement::Block(body) = &**body else { panic!() };
+        let Statement::Fn { body: inner, .. } = &body[1] else { panic!() };
+        let Statement::Block(inner) = &**inner else { panic!() };
+        let Statement::Return(Some(sum)) = &inner[0] else { panic!() };
+        let mut resolutions = vec![];
+        let mut expr = sum;
+        while let Expr::Binary { left, right, .. } = expr {
+            if let Expr::Literal(Literal::Var(_, resolution)) = &**right {
+                resolutions.push(*resolution);
+            }
+            expr = left;
+        }
+        if let Expr::Literal(Literal::Var(_, resolution)) = expr {
+            resolutions.push(*resolution);
+        }
+        // c, b, then a: each function body is a block inside a parameter scope.
+        let resolutions = resolutions.iter().map(|r| format!("{:?}", r)).collect::<Vec<String>>();
+        assert_eq!(resolutions, ["Global", "Local(2)", "Local(3)"]);
+    }
+
+    #[test]
+    fn test_local_functions_may_call_later_ones() {
+        assert!(resolved("{ fn even(n) { return n == 0 or odd(n - 1); } fn odd(n) { return n != 0 and even(n - 1); } }").is_ok());
+        assert!(resolved("{ let f = fn(n) { return n < 1 ? 0 : f(n - 1); }; }").is_ok());
+    }
+}
@@ -0,0 +1,137 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::evaluate::Value;
+
+/// Host-supplied override for how `print` renders values.
+pub type Formatter = Rc<dyn Fn(&Value) -> String>;
+
+/// Where diagnostics such as call traces are written; stderr by default.
+pub type ErrorSink = Rc<RefCell<dyn Write>>;
+
+/// Where `print` output goes; stdout by default.
+pub type OutputSink = Rc<RefCell<dyn Write>>;
+
+/// Interpreter-wide settings and state shared by every scope of a program.
+///
+/// Each `Environment` holds a handle to the same `Runtime`, so options set on
+/// the `Interpreter` are visible from any nested scope during evaluation.
+pub struct Runtime {
+    /// Turn arithmetic that produces NaN or infinity into a runtime error.
+    pub strict_math: bool,
+    /// Report division by zero as an error. When off, it follows IEEE-754:
+    /// `1/0` is infinity and `0/0` is NaN.
+    pub strict_division: bool,
+    /// Make arithmetic and comparisons involving nil yield nil instead of
+    /// raising a type error.
+    pub nil_propagation: bool,
+    /// Pause at `breakpoint()` calls and open an interactive debugger.
+    pub debug: bool,
+    /// Show the variables a closure captured when printing functions.
+    pub debug_print: bool,
+    /// Replaces the built-in `stringify` for `print` when set.
+    pub formatter: Option<Formatter>,
+    /// Cleanup callbacks registered with `on_close`, run when a `with`
+    /// statement over the resource finishes.
+    pub cleanups: Vec<(Value, Value)>,
+    /// Log every function call and return to the error sink.
+    pub trace_calls: bool,
+    /// Number of function calls currently in progress.
+    pub call_depth: usize,
+    /// Calls nested deeper than this fail with a stack overflow error
+    /// rather than exhausting the host's stack.
+    pub max_call_depth: usize,
+    pub error_sink: ErrorSink,
+    /// Time calls to user-defined functions into `profile`.
+    pub profiling: bool,
+    /// Cumulative wall-clock time per function name.
+    pub profile: HashMap<String, Duration>,
+    /// Names of the profiled calls in progress, innermost last.
+    profile_stack: Vec<String>,
+    pub output: OutputSink,
+    /// Print the value of each top-level expression statement, as a REPL
+    /// does. Files run with `run_file` or `import` never echo.
+    pub echo_expressions: bool,
+    /// Canonical paths of the files being run, innermost last. `import` paths
+    /// are resolved against the last, and importing one that is still running
+    /// is a circular import, which is skipped.
+    pub running_files: Vec<PathBuf>,
+    /// Canonical paths of files that ran successfully, so each is imported
+    /// once.
+    pub imported: HashSet<PathBuf>,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Runtime {
+            strict_math: false,
+            strict_division: true,
+            nil_propagation: false,
+            debug: false,
+            debug_print: false,
+            formatter: None,
+            cleanups: vec![],
+            trace_calls: false,
+            call_depth: 0,
+            max_call_depth: 1000,
+            error_sink: Rc::new(RefCell::new(std::io::stderr())),
+            profiling: false,
+            profile: HashMap::new(),
+            profile_stack: vec![],
+            output: Rc::new(RefCell::new(std::io::stdout())),
+            echo_expressions: false,
+            running_files: vec![],
+            imported: HashSet::new(),
+        }
+    }
+}
+
+impl Runtime {
+    pub fn new() -> Self {
+        Runtime::default()
+    }
+
+    /// Writes a trace line to the error sink, indented by the call depth.
+    pub fn trace(&self, line: &str) {
+        // Tracing is best-effort; a failing sink must not abort the program.
+        let _ = writeln!(self.error_sink.borrow_mut(), "{}{}", "  ".repeat(self.call_depth), line);
+    }
+
+    /// Marks a profiled call as started. Returns whether it is the outermost
+    /// call of that function, which is the only one whose time is recorded,
+    /// so recursion isn't counted more than once.
+    pub fn profile_enter(&mut self, name: &str) -> bool {
+        let outermost = !self.profile_stack.iter().any(|active| active == name);
+        self.profile_stack.push(name.to_string());
+        outermost
+    }
+
+    /// Ends the innermost profiled call, adding `elapsed` to its function's
+    /// total if `profile_enter` said it was the outermost call.
+    pub fn profile_exit(&mut self, elapsed: Duration, outermost: bool) {
+        if let Some(name) = self.profile_stack.pop()
+            && outermost
+        {
+            *self.profile.entry(name).or_default() += elapsed;
+        }
+    }
+
+    /// Removes and returns the cleanup callback registered for `resource`.
+    /// Strings, lists and maps are matched by identity, so equal resources
+    /// registered separately keep their own callbacks. Other values have no
+    /// identity and are matched by equality.
+    pub fn take_cleanup(&mut self, resource: &Value) -> Option<Value> {
+        let position = self.cleanups.iter().position(|(registered, _)| match (registered, resource) {
+            (Value::String(a), Value::String(b)) => Rc::ptr_eq(a, b),
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+            (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+            (Value::String(_) | Value::List(_) | Value::Map(_), _) => false,
+            (a, b) => a == b,
+        })?;
+        Some(self.cleanups.remove(position).1)
+    }
+}
@@ -0,0 +1,566 @@
+use crate::expressions::{BinaryOp, Expr, Literal, Statement};
+
+const INDENT: &str = "    ";
+
+// Binding strength of each expression form, mirroring the parser's levels.
+// A child that binds more loosely than its slot allows is parenthesized.
+const ASSIGNMENT: u8 = 0;
+const CONDITIONAL: u8 = 1;
+const OR: u8 = 2;
+const AND: u8 = 3;
+const EQUALITY: u8 = 4;
+const COMPARISON: u8 = 5;
+const RANGE: u8 = 6;
+const TERM: u8 = 7;
+const FACTOR: u8 = 8;
+const UNARY: u8 = 9;
+const POWER: u8 = 10;
+const POSTFIX: u8 = 11;
+
+fn binary_precedence(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Or => OR,
+        BinaryOp::And => AND,
+        BinaryOp::EqualEqual | BinaryOp::NotEqual => EQUALITY,
+        BinaryOp::Greater | BinaryOp::GreaterEqual | BinaryOp::Less | BinaryOp::LessEqual => COMPARISON,
+        BinaryOp::Plus | BinaryOp::Minus => TERM,
+        BinaryOp::Multiply | BinaryOp::Divide => FACTOR,
+        BinaryOp::Power => POWER,
+        BinaryOp::Range => RANGE,
+    }
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Assign { .. } | Expr::Let { .. } => ASSIGNMENT,
+        Expr::Binary { op, .. } => binary_precedence(*op),
+        Expr::Conditional { .. } => CONDITIONAL,
+        Expr::Unary { .. } => UNARY,
+        _ => POSTFIX,
+    }
+}
+
+// Renders `expr` for a slot that needs at least `min` precedence.
+fn operand(expr: &Expr, min: u8) -> String {
+    if precedence(expr) < min {
+        format!("({})", expr_to_source(expr))
+    } else {
+        expr_to_source(expr)
+    }
+}
+
+fn comma_separated(exprs: &[Expr]) -> String {
+    exprs.iter().map(expr_to_source).collect::<Vec<String>>().join(", ")
+}
+
+// The inverse of the scanner's string escapes.
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            '\\' | '"' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            // Only a `$` before `{` would start an interpolation.
+            '$' if chars.peek() == Some(&'{') => escaped.push_str("\\$"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders an expression as Spade source, adding parentheses only where
+/// precedence requires them.
+pub fn expr_to_source(expr: &Expr) -> String {
+    match expr {
+        // `**` is right-associative and takes a unary exponent.
+        Expr::Binary { left, op: BinaryOp::Power, right, .. } => {
+            format!("{} ** {}", operand(left, POSTFIX), operand(right, UNARY))
+        },
+        // `..` isn't associative, so a nested range is parenthesized on either side.
+        Expr::Binary { left, op: BinaryOp::Range, right, .. } => {
+            format!("{}..{}", operand(left, RANGE + 1), operand(right, RANGE + 1))
+        },
+        Expr::Binary { left, op, right, .. } => {
+            // The other binary operators are left-associative, so only the right operand
+            // needs parentheses at equal precedence.
+            let precedence = binary_precedence(*op);
+            format!("{} {} {}", operand(left, precedence), op, operand(right, precedence + 1))
+        },
+        Expr::Unary { op, expr, .. } => format!("{}{}", op, operand(expr, UNARY)),
+        Expr::Literal(literal) => match literal {
+            Literal::Nil => "nil".to_string(),
+            Literal::Integer(n) => n.to_string(),
+            // Debug output keeps a `.0` or exponent, so it scans back as a float.
+            Literal::Number(n) => format!("{:?}", n),
+            Literal::String(s) => format!("\"{}\"", escape(s)),
+            Literal::Bool(b) => b.to_string(),
+            Literal::Var(token, _) => token.lexeme.clone(),
+        },
+        Expr::Grouping(expr) => format!("({})", expr_to_source(expr)),
+        Expr::Conditional { condition, then_branch, else_branch } => format!(
+            "{} ? {} : {}",
+            operand(condition, CONDITIONAL + 1),
+            expr_to_source(then_branch),
+            expr_to_source(else_branch),
+        ),
+        Expr::Let { name, value, body } => {
+            format!("let {} = {} in {}", name, expr_to_source(value), expr_to_source(body))
+        },
+        Expr::Assign { token, value, .. } => format!("{} = {}", token.lexeme, expr_to_source(value)),
+        Expr::Call { callee, arguments, .. } => format!("{}({})", operand(callee, POSTFIX), comma_separated(arguments)),
+        Expr::Get { object, name } => format!("{}.{}", operand(object, POSTFIX), name.lexeme),
+        Expr::List(elements) => format!("[{}]", comma_separated(elements)),
+        Expr::Spread(expr) => format!("...{}", expr_to_source(expr)),
+        Expr::Index { object, index, .. } => format!("{}[{}]", operand(object, POSTFIX), expr_to_source(index)),
+        // Nested blocks inside the body are indented relative to column 0.
+        Expr::Function { parameters, body } => format!("fn({}) {}", parameters.join(", "), statement_source(body, 0)),
+        // Text parts are string literals; anything else is embedded.
+        Expr::Interpolation(parts) => {
+            let parts = parts.iter()
+                .map(|part| match part {
+                    Expr::Literal(Literal::String(text)) => escape(text),
+                    expr => format!("${{{}}}", expr_to_source(expr)),
+                })
+                .collect::<String>();
+            format!("\"{}\"", parts)
+        },
+        Expr::Map(entries) => {
+            let entries = entries.iter()
+                .map(|(key, value)| format!("{}: {}", expr_to_source(key), expr_to_source(value)))
+                .collect::<Vec<String>>();
+            format!("{{{}}}", entries.join(", "))
+        },
+    }
+}
+
+/// Renders a statement as runnable Spade source, with blocks indented by four
+/// spaces per level.
+pub fn to_source(stmt: &Statement) -> String {
+    statement_source(stmt, 0)
+}
+
+// The first line is left unindented so callers can place the statement after
+// other text, e.g. `else `.
+fn statement_source(stmt: &Statement, depth: usize) -> String {
+    match stmt {
+        Statement::Expression(expr) => format!("{};", expr_to_source(expr)),
+        Statement::Print(exprs) => {
+            format!("print {};", exprs.iter().map(expr_to_source).collect::<Vec<String>>().join(", "))
+        },
+        Statement::Block(statements) => {
+            if statements.is_empty() {
+                return "{}".to_string();
+            }
+            let mut source = "{\n".to_string();
+            for statement in statements {
+                source.push_str(&INDENT.repeat(depth + 1));
+                source.push_str(&statement_source(statement, depth + 1));
+                source.push('\n');
+            }
+            source.push_str(&INDENT.repeat(depth));
+            source.push('}');
+            source
+        },
+        Statement::VarDec { name, initializer, mutable } => {
+            let keyword = if *mutable { "let mut" } else { "let" };
+            match initializer {
+                Some(expr) => format!("{} {} = {};", keyword, name, expr_to_source(expr)),
+                None => format!("{} {};", keyword, name),
+            }
+        },
+        Statement::If { condition, then_branch, else_branch } => {
+            let mut source = format!("if ({}) {}", expr_to_source(condition), statement_source(then_branch, depth));
+            if let Some(else_branch) = else_branch {
+                source.push_str(" else ");
+                source.push_str(&statement_source(else_branch, depth));
+            }
+            source
+        },
+        Statement::Fn { name, parameters, body } => {
+            format!("fn {}({}) {}", name, parameters.join(", "), statement_source(body, depth))
+        },
+        Statement::Return(expr) => match expr {
+            Some(expr) => format!("return {};", expr_to_source(expr)),
+            None => "return;".to_string(),
+        },
+        Statement::While { condition, body, increment: None } => {
+            format!("while ({}) {}", expr_to_source(condition), statement_source(body, depth))
+        },
+        Statement::While { condition, body, increment: Some(increment) } => format!(
+            "for (; {}; {}) {}",
+            expr_to_source(condition),
+            expr_to_source(increment),
+            statement_source(body, depth),
+        ),
+        Statement::ForIn { name, iterable, body, .. } => {
+            format!("for ({} in {}) {}", name, expr_to_source(iterable), statement_source(body, depth))
+        },
+        Statement::Break => "break;".to_string(),
+        Statement::Continue => "continue;".to_string(),
+        Statement::Import { path, .. } => format!("import \"{}\";", escape(path)),
+        Statement::Match { subject, arms } => {
+            let mut source = format!("match ({}) {{\n", expr_to_source(subject));
+            for arm in arms {
+                source.push_str(&INDENT.repeat(depth + 1));
+                match &arm.pattern {
+                    Some(pattern) => source.push_str(&expr_to_source(pattern)),
+                    None => source.push('_'),
+                }
+                if let Some(guard) = &arm.guard {
+                    source.push_str(&format!(" if {}", expr_to_source(guard)));
+                }
+                source.push_str(&format!(" => {}\n", statement_source(&arm.body, depth + 1)));
+            }
+            source.push_str(&INDENT.repeat(depth));
+            source.push('}');
+            source
+        },
+        Statement::With { name, resource, body } => {
+            format!("with ({} = {}) {}", name, expr_to_source(resource), statement_source(body, depth))
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expressions::{ast_size, walk_expr, walk_statement, Line, MatchArm, Resolution, UnaryOp, Visitor};
+    use crate::token::{scan_tokens, Token, TokenType};
+    use crate::test_support::Rng;
+    use crate::tree::parse_stmt;
+    use std::rc::Rc;
+
+    const NAMES: &[&str] = &["a", "b", "total", "x_1"];
+
+    // Import paths, including characters that must be escaped when printed.
+    const PATHS: &[&str] = &["lib/math.spade", "say \"hi\".spade", "${x}\\n.spade"];
+
+    const OPERATORS: &[BinaryOp] = &[
+        BinaryOp::Or, BinaryOp::And, BinaryOp::EqualEqual, BinaryOp::NotEqual, BinaryOp::Greater, BinaryOp::GreaterEqual,
+        BinaryOp::Less, BinaryOp::LessEqual, BinaryOp::Plus, BinaryOp::Minus,
+        BinaryOp::Multiply, BinaryOp::Divide, BinaryOp::Power, BinaryOp::Range,
+    ];
+
+    fn name(rng: &mut Rng) -> String {
+        NAMES[rng.below(NAMES.len())].to_string()
+    }
+
+    fn identifier(name: String) -> Token {
+        Token { token_type: TokenType::Identifier, lexeme: name, literal: None, line: 1, column: 1 }
+    }
+
+    // Generates ASTs the parser could have produced: every child that binds
+    // more loosely than its position allows is wrapped in a `Grouping`. The
+    // `size` budget bounds the tree, so smaller sizes give smaller cases.
+    struct Generator {
+        rng: Rng,
+    }
+
+    impl Generator {
+        fn in_slot(&mut self, size: usize, min: u8) -> Expr {
+            let expr = self.expr(size);
+            if precedence(&expr) < min {
+                Expr::Grouping(Box::new(expr))
+            } else {
+                expr
+            }
+        }
+
+        fn exprs(&mut self, size: usize) -> Vec<Expr> {
+            let count = self.rng.below(size.min(3) + 1);
+            (0..count).map(|_| self.expr(size / 2)).collect()
+        }
+
+        fn literal(&mut self) -> Expr {
+            Expr::Literal(match self.rng.below(6) {
+                0 => Literal::Nil,
+                1 => Literal::Bool(self.rng.below(2) == 0),
+                2 => Literal::Number(self.rng.below(1000) as f64 / 4.0),
+                3 => Literal::String(name(&mut self.rng)),
+                4 => Literal::Integer(self.rng.below(1000) as i64),
+                _ => Literal::Var(identifier(name(&mut self.rng)), Resolution::Unresolved),
+            })
+        }
+
+        fn expr(&mut self, size: usize) -> Expr {
+            if size == 0 {
+                return self.literal();
+            }
+            let size = size - 1;
+            match self.rng.below(13) {
+                0 | 1 => {
+                    let op = OPERATORS[self.rng.below(OPERATORS.len())];
+                    let (left, right) = match op {
+                        BinaryOp::Power => (POSTFIX, UNARY),
+                        op => (binary_precedence(op), binary_precedence(op) + 1),
+                    };
+                    Expr::Binary {
+                        left: Box::new(self.in_slot(size / 2, left)),
+                        op,
+                        right: Box::new(self.in_slot(size / 2, right)),
+                        line: Line(1),
+                    }
+                },
+                2 => {
+                    let op = if self.rng.below(2) == 0 { UnaryOp::Minus } else { UnaryOp::Not };
+                    Expr::Unary { op, expr: Box::new(self.in_slot(size, UNARY)), line: Line(1) }
+                },
+                3 => Expr::Grouping(Box::new(self.expr(size))),
+                4 => {
+                    let mut arguments = self.exprs(size);
+                    if self.rng.below(3) == 0 {
+                        arguments.push(Expr::Spread(Box::new(self.expr(size / 2))));
+                    }
+                    Expr::Call { callee: Box::new(self.in_slot(size / 2, POSTFIX)), arguments, line: Line(1) }
+                },
+                5 => Expr::Get {
+                    object: Box::new(self.in_slot(size, POSTFIX)),
+                    name: identifier(name(&mut self.rng)),
+                },
+                6 => Expr::List(self.exprs(size)),
+                7 => Expr::Index {
+                    object: Box::new(self.in_slot(size / 2, POSTFIX)),
+                    index: Box::new(self.expr(size / 2)),
+                    line: Line(1),
+                },
+                8 => Expr::Conditional {
+                    condition: Box::new(self.in_slot(size / 3, CONDITIONAL + 1)),
+                    then_branch: Box::new(self.expr(size / 3)),
+                    else_branch: Box::new(self.expr(size / 3)),
+                },
+                9 => Expr::Function {
+                    parameters: (0..self.rng.below(3)).map(|_| name(&mut self.rng)).collect(),
+                    body: Rc::new(self.block(size)),
+                },
+                // Text and embedded expressions alternate, as the parser
+                // produces them; an embedded string literal would print as text.
+                10 => Expr::Interpolation((0..self.rng.below(3) + 2).map(|i| match i % 2 {
+                    0 => Expr::Literal(Literal::String(format!("{}${{", name(&mut self.rng)))),
+                    _ => match self.expr(size / 2) {
+                        Expr::Literal(Literal::String(_)) => Expr::Literal(Literal::Nil),
+                        expr => expr,
+                    },
+                }).collect()),
+                11 => Expr::Assign {
+                    token: identifier(name(&mut self.rng)),
+                    value: Box::new(self.expr(size)),
+                    resolution: Resolution::Unresolved,
+                },
+                12 if self.rng.below(2) == 0 => Expr::Let {
+                    name: name(&mut self.rng),
+                    value: Box::new(self.expr(size / 2)),
+                    body: Box::new(self.expr(size / 2)),
+                },
+                _ => {
+                    let count = self.rng.below(3);
+                    Expr::Map((0..count).map(|_| (self.literal(), self.expr(size / 2))).collect())
+                },
+            }
+        }
+
+        fn block(&mut self, size: usize) -> Statement {
+            let count = self.rng.below(size.min(3) + 1);
+            Statement::Block((0..count).map(|_| self.statement(size / 2)).collect())
+        }
+
+        fn statement(&mut self, size: usize) -> Statement {
+            let size = size.saturating_sub(1);
+            match self.rng.below(10) {
+                // An expression statement can't start with `{`, which would
+                // begin a block, so use a call.
+                0 => Statement::Expression(Expr::Call {
+                    callee: Box::new(Expr::Literal(Literal::Var(identifier(name(&mut self.rng)), Resolution::Unresolved))),
+                    arguments: self.exprs(size),
+                    line: Line(1),
+                }),
+                1 => Statement::Print((0..=self.rng.below(3)).map(|_| self.expr(size / 2)).collect()),
+                2 => Statement::VarDec {
+                    name: name(&mut self.rng),
+                    initializer: if self.rng.below(4) == 0 { None } else { Some(self.expr(size)) },
+                    mutable: self.rng.below(2) == 0,
+                },
+                // Branches are blocks (or `else if`) so a dangling `else`
+                // can't attach to a different `if` when re-parsed.
+                3 => {
+                    let else_branch = match self.rng.below(3) {
+                        0 => None,
+                        1 => Some(Box::new(self.block(size / 2))),
+                        _ => Some(Box::new(Statement::If {
+                            condition: self.expr(size / 2),
+                            then_branch: Box::new(self.block(size / 2)),
+                            else_branch: None,
+                        })),
+                    };
+                    Statement::If {
+                        condition: self.expr(size / 2),
+                        then_branch: Box::new(self.block(size / 2)),
+                        else_branch,
+                    }
+                },
+                4 => Statement::Fn {
+                    name: name(&mut self.rng),
+                    parameters: (0..self.rng.below(3)).map(|_| name(&mut self.rng)).collect(),
+                    body: Rc::new(self.block(size)),
+                },
+                5 => Statement::Return(if self.rng.below(3) == 0 { None } else { Some(self.expr(size)) }),
+                6 if self.rng.below(2) == 0 => Statement::While {
+                    condition: self.expr(size / 2),
+                    body: Box::new(self.block(size / 2)),
+                    increment: if self.rng.below(2) == 0 { None } else { Some(self.expr(size / 2)) },
+                },
+                6 => Statement::With {
+                    name: name(&mut self.rng),
+                    resource: self.expr(size / 2),
+                    body: Box::new(self.block(size / 2)),
+                },
+                7 if self.rng.below(2) == 0 => Statement::Match {
+                    subject: self.expr(size / 2),
+                    arms: (0..self.rng.below(3)).map(|_| MatchArm {
+                        pattern: if self.rng.below(3) == 0 { None } else { Some(self.expr(size / 4)) },
+                        guard: if self.rng.below(2) == 0 { None } else { Some(self.expr(size / 4)) },
+                        body: self.block(size / 4),
+                    }).collect(),
+                },
+                8 => Statement::ForIn {
+                    name: name(&mut self.rng),
+                    iterable: self.expr(size / 2),
+                    body: Box::new(self.block(size / 2)),
+                    line: Line(1),
+                },
+                9 => Statement::Import {
+                    path: PATHS[self.rng.below(PATHS.len())].to_string(),
+                    line: Line(1),
+                },
+                _ => self.block(size),
+            }
+        }
+    }
+
+    fn round_trips(statement: &Statement) -> bool {
+        let source = to_source(statement);
+        match scan_tokens(source).and_then(parse_stmt) {
+            Ok(parsed) => parsed.len() == 1 && parsed[0] == *statement,
+            Err(_) => false,
+        }
+    }
+
+    // Every proper part of `statement` as a statement of its own: nested
+    // statements as they are, and expressions wrapped in a `print`.
+    fn parts(statement: &Statement) -> Vec<Statement> {
+        struct Parts(Vec<Statement>);
+
+        impl Visitor for Parts {
+            fn visit_statement(&mut self, statement: &Statement) {
+                self.0.push(statement.clone());
+                walk_statement(self, statement);
+            }
+
+            fn visit_expr(&mut self, expr: &Expr) {
+                // A spread is only valid as a call argument.
+                if !matches!(expr, Expr::Spread(_)) {
+                    self.0.push(Statement::Print(vec![expr.clone()]));
+                }
+                walk_expr(self, expr);
+            }
+        }
+
+        let mut parts = Parts(vec![]);
+        walk_statement(&mut parts, statement);
+        let size = ast_size(std::slice::from_ref(statement));
+        parts.0.into_iter().filter(|part| ast_size(std::slice::from_ref(part)) < size).collect()
+    }
+
+    // Repeatedly replaces a failing statement with its smallest part that
+    // still fails, until no part does.
+    fn shrink(mut statement: Statement) -> Statement {
+        while let Some(smaller) = parts(&statement).into_iter()
+            .filter(|part| !round_trips(part))
+            .min_by_key(|part| ast_size(std::slice::from_ref(part)))
+        {
+            statement = smaller;
+        }
+        statement
+    }
+
+    #[test]
+    fn test_source_round_trip() {
+        for seed in 1..500u64 {
+            let statement = Generator { rng: Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15)) }.statement(12);
+            if !round_trips(&statement) {
+                let smallest = shrink(statement);
+                panic!("seed {} does not round-trip:\n{}\n{}", seed, to_source(&smallest), smallest);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shrink_finds_the_failing_part() {
+        // A hand-built tree the parser can't produce: the assignment needs
+        // parentheses as a left operand, and printing drops them.
+        let var = |name: &str| Box::new(Expr::Literal(Literal::Var(identifier(name.to_string()), Resolution::Unresolved)));
+        let assign = Expr::Assign { token: identifier("a".to_string()), value: var("b"), resolution: Resolution::Unresolved };
+        let sum = Expr::Binary { left: Box::new(assign), op: BinaryOp::Plus, right: var("c"), line: Line(1) };
+        let statement = Statement::If {
+            condition: *var("x"),
+            then_branch: Box::new(Statement::Block(vec![Statement::Print(vec![*var("y"), sum.clone()])])),
+            else_branch: None,
+        };
+        assert!(!round_trips(&statement));
+        assert_eq!(shrink(statement), Statement::Print(vec![sum]));
+    }
+
+    #[test]
+    fn test_program_round_trip() {
+        let program = "
+            let mut total = 0;
+            fn fibonacci(n) { if (n < 2) { return n; } else return fibonacci(n - 1) + fibonacci(n - 2); }
+            for (let i = 0; i < 10; i += 1) { if (i == 3) continue; total = total + fibonacci(i) * (2 - i); }
+            print total > 0 ? \"positive\" : -total ** 2;
+            match (total) { 0 => print \"zero\"; _ if total > 100 => { print fibonacci(...[2]); } }
+        ";
+        let statements = parse_stmt(scan_tokens(program.to_string()).unwrap()).unwrap();
+        let printed = statements.iter().map(to_source).collect::<Vec<String>>().join("\n");
+        assert_eq!(parse_stmt(scan_tokens(printed.clone()).unwrap()).unwrap(), statements, "{}", printed);
+    }
+
+    #[test]
+    fn test_assignment_in_else_branch_round_trips() {
+        for (program, printed) in [("c ? x : a = 2;", "c ? x : a = 2;"), ("a ||= 2;", "a ? a : a = 2;")] {
+            let statements = parse_stmt(scan_tokens(program.to_string()).unwrap()).unwrap();
+            assert_eq!(to_source(&statements[0]), printed);
+            assert!(round_trips(&statements[0]), "{}", printed);
+        }
+    }
+
+    #[test]
+    fn test_parenthesizes_only_when_needed() {
+        let number = |n| Box::new(Expr::Literal(Literal::Integer(n)));
+        let sum = Expr::Binary { left: number(1), op: BinaryOp::Plus, right: number(2), line: Line(1) };
+        let product = Expr::Binary { left: Box::new(sum.clone()), op: BinaryOp::Multiply, right: number(3), line: Line(1) };
+        assert_eq!(expr_to_source(&product), "(1 + 2) * 3");
+        let difference = Expr::Binary { left: Box::new(sum.clone()), op: BinaryOp::Minus, right: Box::new(sum), line: Line(1) };
+        assert_eq!(expr_to_source(&difference), "1 + 2 - (1 + 2)");
+    }
+
+    #[test]
+    fn test_string_escapes_round_trip() {
+        let statement = Statement::Print(vec![Expr::Literal(Literal::String("tab\there \"quoted\" \\ \n".to_string()))]);
+        assert_eq!(to_source(&statement), r#"print "tab\there \"quoted\" \\ \n";"#);
+        assert!(round_trips(&statement));
+
+        let statement = Statement::Print(vec![Expr::Literal(Literal::String("${not code} $5".to_string()))]);
+        assert_eq!(to_source(&statement), r#"print "\${not code} $5";"#);
+        assert!(round_trips(&statement));
+
+        let statement = Statement::Import { path: "a\"b".to_string(), line: Line(1) };
+        assert_eq!(to_source(&statement), r#"import "a\"b";"#);
+        assert!(round_trips(&statement));
+    }
+}
@@ -0,0 +1,267 @@
+use std::io::BufRead;
+use std::path::Path;
+
+use crate::error::{Diagnostic, SpadeError};
+use crate::evaluate::{NativeFunction, Value};
+use crate::expressions::Statement;
+use crate::interpreter::Interpreter;
+use crate::resolver::resolve;
+use crate::token::scan_tokens;
+use crate::tree::{parse_program, parse_stmt};
+
+/// The one-stop embedding API: scans, parses, and runs Spade source against
+/// a persistent interpreter, so definitions survive between calls.
+///
+/// ```
+/// use spade::Spade;
+/// use spade::evaluate::Value;
+///
+/// let mut spade = Spade::new();
+/// spade.run("let x = 20;").unwrap();
+/// let value = spade.run("x + 22;").unwrap();
+/// assert!(matches!(value, Value::Integer(42)));
+/// ```
+pub struct Spade {
+    interpreter: Interpreter,
+}
+
+impl Default for Spade {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Spade {
+    pub fn new() -> Self {
+        Spade {
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    /// Wraps an interpreter configured with its `with_*` options.
+    pub fn with_interpreter(interpreter: Interpreter) -> Self {
+        Spade { interpreter }
+    }
+
+    /// Runs the source, returning the value of its last statement.
+    pub fn run(&mut self, src: &str) -> Result<Value, SpadeError> {
+        let statements = parse_source(src)?;
+        self.interpreter.run(statements)
+    }
+
+    /// Runs a source file. `import` statements in it are resolved relative to
+    /// the file's directory.
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<Value, SpadeError> {
+        self.interpreter.run_file(path.as_ref())
+    }
+
+    /// Runs an interactive session over `input`; see `Interpreter::repl`.
+    pub fn repl(&mut self, input: impl BufRead) -> Result<(), SpadeError> {
+        self.interpreter.repl(input)
+    }
+
+    /// Scans, parses and resolves the source without executing it, reporting
+    /// every syntax error found, or else the first resolution error.
+    pub fn check(&self, src: &str) -> Result<(), Vec<Diagnostic>> {
+        let diagnostics = |errors: Vec<SpadeError>| {
+            errors.into_iter().map(|e| Diagnostic { message: e.to_string() }).collect::<Vec<_>>()
+        };
+        let tokens = scan_tokens(src.to_string()).map_err(|e| diagnostics(vec![e]))?;
+        let mut statements = parse_program(tokens).map_err(diagnostics)?;
+        resolve(&mut statements).map_err(|e| diagnostics(vec![e]))
+    }
+
+    pub fn define_native(&mut self, name: &str, function: NativeFunction) {
+        self.interpreter.define_native(name, function);
+    }
+}
+
+/// Runs a complete program in a fresh interpreter, reporting scan, parse and
+/// runtime failures through the one error type.
+///
+/// ```
+/// spade::run("let x = 2; print x * 3;").unwrap();
+/// assert!(spade::run("print 1 +;").is_err());
+/// ```
+pub fn run(src: &str) -> Result<(), SpadeError> {
+    Spade::new().run(src).map(|_| ())
+}
+
+pub(crate) fn parse_source(src: &str) -> Result<Vec<Statement>, SpadeError> {
+    parse_stmt(scan_tokens(src.to_string())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+
+    fn double(_env: &mut Environment, args: Vec<Value>) -> Result<Value, SpadeError> {
+        match args.as_slice() {
+            [Value::Integer(n)] => Ok(Value::Integer(n * 2)),
+            _ => Err(SpadeError::runtime_error("double() expects a number".to_string(), 0)),
+        }
+    }
+
+    #[test]
+    fn test_run_persists_state() {
+        let mut spade = Spade::new();
+        assert_eq!(spade.run("let x = 2;").unwrap(), Value::Nil);
+        assert!(matches!(spade.run("x * 3;").unwrap(), Value::Integer(6)));
+    }
+
+    #[test]
+    fn test_run_end_to_end() {
+        let output = std::rc::Rc::new(std::cell::RefCell::new(Vec::<u8>::new()));
+        let mut spade = Spade::with_interpreter(Interpreter::new().with_writer(output.clone()));
+        assert!(spade.run("let x = 2; print x * 3;").is_ok());
+        assert_eq!(*output.borrow(), b"6\n");
+        assert!(matches!(run("let s = \"open;"), Err(SpadeError::ScanError { line: 1, column: 9, .. })));
+        assert!(matches!(run("let = 1;"), Err(SpadeError::ParseError { line: 1, .. })));
+        assert!(matches!(run("print y;"), Err(SpadeError::RuntimeError { .. })));
+    }
+
+    #[test]
+    fn test_errors_share_one_type() {
+        let scan: Result<Vec<_>, SpadeError> = scan_tokens("\"open".to_string());
+        let parse: Result<Vec<_>, SpadeError> = parse_stmt(scan_tokens("let = 1;".to_string()).unwrap());
+        let runtime: Result<Value, SpadeError> = Spade::new().run("\n print y;");
+        let messages = [scan.unwrap_err(), parse.unwrap_err(), runtime.unwrap_err()].map(|e| e.to_string());
+        assert_eq!(messages, [
+            "Unterminated string [line 1, column 1]",
+            "'let' assignment must be provided a name [line 1, column 5]",
+            "Undefined variable 'y'. [line 2]",
+        ]);
+    }
+
+    #[test]
+    fn test_check() {
+        let spade = Spade::new();
+        assert!(spade.check("let x = 1; print x;").is_ok());
+        let diagnostics = spade.check("let = 1;").unwrap_err();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(spade.check("let s = \"open;").is_err());
+        let diagnostics = spade.check("print 1 +;\nlet x = 2;\nprint x x;").unwrap_err();
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[1].message.ends_with("[line 3, column 9]"), "{:?}", diagnostics);
+        let diagnostics = spade.check("{ print y; let y = 1; }").unwrap_err();
+        assert_eq!(diagnostics[0].message, "Local variable 'y' is used before its declaration [line 1, column 9]");
+    }
+
+    #[test]
+    fn test_strings_are_shared_not_copied() {
+        let mut spade = Spade::new();
+        spade.run("
+            let big = pad_right(\"\", 100000, \"x\");
+            fn id(s) { return s; }
+            fn relay(s, n) { let mut out = s; for (let mut i = 0; i < n; i += 1) { out = id(out); } return out; }
+        ").unwrap();
+        let (Value::String(big), Value::String(relayed)) = (spade.run("big;").unwrap(), spade.run("relay(big, 1000);").unwrap()) else {
+            panic!("expected strings");
+        };
+        assert_eq!(relayed.len(), 100000);
+        assert!(std::rc::Rc::ptr_eq(&big, &relayed));
+        assert_eq!(spade.run("relay(big, 1000) == big;").unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_scopes_holding_closures_are_freed() {
+        let mut spade = Spade::new();
+        let Value::String(text) = spade.run("let text = \"captured\"; text;").unwrap() else {
+            panic!("expected a string");
+        };
+        let references = std::rc::Rc::strong_count(&text);
+        spade.run("
+            fn local(s) { fn f() { return s; } let g = fn() { return s; }; return nil; }
+            fn make(s) { fn f() { return s; } return f; }
+            local(text);
+            let mut kept = make(text);
+        ").unwrap();
+        // The call scope holding `kept`'s captured `s` lives while `kept` does.
+        assert_eq!(std::rc::Rc::strong_count(&text), references + 1);
+        assert_eq!(spade.run("kept();").unwrap(), Value::String(text.clone()));
+        spade.run("kept = nil;").unwrap();
+        assert_eq!(std::rc::Rc::strong_count(&text), references);
+    }
+
+    #[test]
+    fn test_define_native() {
+        let mut spade = Spade::new();
+        spade.define_native("double", double);
+        assert!(matches!(spade.run("double(21);").unwrap(), Value::Integer(42)));
+    }
+
+    /// A fresh directory under the system temp dir for file-based tests.
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("spade-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_import_relative_to_importing_file() {
+        let dir = temp_dir("import");
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(dir.join("lib/math.spade"), "fn square(x) { return x * x; }").unwrap();
+        std::fs::write(dir.join("main.spade"), "import \"lib/math.spade\"; square(7);").unwrap();
+
+        let mut spade = Spade::new();
+        assert!(matches!(spade.run_file(dir.join("main.spade")).unwrap(), Value::Integer(49)));
+        // Imported definitions land in the global scope.
+        assert!(matches!(spade.run("square(3);").unwrap(), Value::Integer(9)));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_circular_import() {
+        let dir = temp_dir("circular");
+        std::fs::write(dir.join("a.spade"), "import \"b.spade\"; fn a() { return 1; }").unwrap();
+        std::fs::write(dir.join("b.spade"), "import \"a.spade\"; fn b() { return 2; }").unwrap();
+
+        let mut spade = Spade::new();
+        spade.run_file(dir.join("a.spade")).unwrap();
+        assert!(matches!(spade.run("a() + b();").unwrap(), Value::Integer(3)));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_file_runs_again() {
+        let dir = temp_dir("rerun");
+        std::fs::write(dir.join("main.spade"), "fn f() { return 1; } 42;").unwrap();
+
+        let mut spade = Spade::new();
+        assert!(matches!(spade.run_file(dir.join("main.spade")).unwrap(), Value::Integer(42)));
+        assert!(matches!(spade.run_file(dir.join("main.spade")).unwrap(), Value::Integer(42)));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_error_names_file_and_line() {
+        let dir = temp_dir("import-error");
+        std::fs::write(dir.join("main.spade"), "let x = 1;\nimport \"lib.spade\";").unwrap();
+        std::fs::write(dir.join("lib.spade"), "fn f() {\n  return 1\n}").unwrap();
+
+        let mut spade = Spade::new();
+        assert_eq!(
+            spade.run_file(dir.join("main.spade")).unwrap_err().to_string(),
+            "Cannot import 'lib.spade': Expect ';' after return value [line 3, column 1] [line 2]",
+        );
+        // A file that failed to import isn't marked as imported, so it is
+        // retried once fixed.
+        std::fs::write(dir.join("lib.spade"), "fn f() {\n  return 1;\n}").unwrap();
+        spade.run_file(dir.join("main.spade")).unwrap();
+        assert!(matches!(spade.run("f();").unwrap(), Value::Integer(1)));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_missing_file() {
+        let mut spade = Spade::new();
+        match spade.run("import \"no/such/file.spade\";") {
+            Err(SpadeError::RuntimeError { message, .. }) => {
+                assert!(message.starts_with("Cannot import 'no/such/file.spade'"), "{}", message)
+            },
+            other => panic!("expected an import error, got {:?}", other),
+        }
+    }
+}
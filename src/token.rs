@@ -1,6 +1,4 @@
-use anyhow::Error;
-
-use crate::token;
+use crate::error::SpadeError;
 
 #[derive(
     PartialEq,
@@ -12,20 +10,34 @@ pub enum TokenType {
     RightParen, // }
     LeftBrace, //
     RightBrace, 
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
+    DotDot,
+    Ellipsis,
     // Math
     Minus,
+    MinusEqual,
     Plus,
+    PlusEqual,
     Slash,
+    SlashEqual,
     Star,
+    StarEqual,
+    StarStar,
     // General
     Semicolon,
+    Colon,
+    Question,
     // Equality
     Bang,
     BangEqual,
     Equal,
     EqualEqual,
+    FatArrow,
+    QuestionQuestionEqual,
+    PipePipeEqual,
     Greater,
     GreaterEqual,
     Less,
@@ -36,12 +48,17 @@ pub enum TokenType {
     Number,
     // KW
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fn,
     For,
     If,
+    Import,
+    In,
+    Match,
     Nil,
     Or,
     Print,
@@ -49,8 +66,11 @@ pub enum TokenType {
     Super,
     This,
     True,
+    Unless,
     Let,
+    Mut,
     While,
+    With,
     EOF
 }
 
@@ -58,12 +78,17 @@ pub enum TokenType {
 pub fn match_reserved(str: &str) -> Option<TokenType> {
     let x = match str {
         "and" => TokenType::And,
+        "break" => TokenType::Break,
         "class" => TokenType::Class,
+        "continue" => TokenType::Continue,
         "else" => TokenType::Else,
         "false" => TokenType::False,
         "for" => TokenType::For,
         "fn" => TokenType::Fn,
         "if" => TokenType::If,
+        "import" => TokenType::Import,
+        "in" => TokenType::In,
+        "match" => TokenType::Match,
         "nil" => TokenType::Nil,
         "or" => TokenType::Or,
         "print" => TokenType::Print,
@@ -71,13 +96,16 @@ pub fn match_reserved(str: &str) -> Option<TokenType> {
         "super" => TokenType::Super,
         "this" => TokenType::This,
         "true" => TokenType::True,
+        "unless" => TokenType::Unless,
         "let" => TokenType::Let,
+        "mut" => TokenType::Mut,
         "while" => TokenType::While,
+        "with" => TokenType::With,
         _ => {
             return None;
         }
     };
-    return Some(x);
+    Some(x)
 }
 
 #[derive(Clone, Debug)]
@@ -86,19 +114,50 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Option<Literal>,
     pub line: usize,
+    /// Column of the token's first character, counting characters from 1.
+    pub column: usize,
+}
+
+// Tokens compare by what they say, not where they were found, so ASTs parsed
+// from differently laid out source are still equal.
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.token_type == other.token_type && self.lexeme == other.lexeme && self.literal == other.literal
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum Literal {
     String(String),
     Number(f64),
+    /// A number written without a fraction or exponent, e.g. `10` or `10i`.
+    Integer(i64),
+    /// A string containing `${ ... }`, split into its text and the tokens of
+    /// each embedded expression.
+    Interpolation(Box<[StringPart]>),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum StringPart {
+    Text(String),
+    Code(Vec<Token>),
 }
 
+// Strings nested inside interpolations deeper than this are rejected, since
+// each level recurses on the native stack.
+const MAX_INTERPOLATION_DEPTH: usize = 64;
+
 struct Scanner {
     source: String,
     start: usize,
     current: usize,
     line: usize,
+    /// The line `start` is on; `line` moves on inside multi-line tokens.
+    start_line: usize,
+    /// The column `start` is at.
+    start_column: usize,
+    /// Number of `${ ... }` segments being scanned.
+    interpolation_depth: usize,
 }
 
 macro_rules! ternary {
@@ -108,11 +167,11 @@ macro_rules! ternary {
 }
 
 fn is_digit(c: char) -> bool {
-    c >= '0' && c <= '9'
+    c.is_ascii_digit()
 }
 
 fn is_alpha(c: char) -> bool {
-    (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+    c.is_ascii_alphabetic() || c == '_'
 }
 
 fn is_alphanumeric(c: char) -> bool {
@@ -122,90 +181,200 @@ fn is_alphanumeric(c: char) -> bool {
 
 impl Scanner {
     pub fn new(source: String) -> Self {
-        return Scanner {
-            source: source,
+        Scanner {
+            source,
             start: 0,
             current: 0,
             line: 1,
-        };
+            start_line: 1,
+            start_column: 1,
+            interpolation_depth: 0,
+        }
+    }
+
+    // Builds an error that points at the byte `offset`, which is on `line`.
+    // Columns count characters from 1.
+    fn error_at(&self, offset: usize, line: usize, message: String) -> SpadeError {
+        let line_start = self.source[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+        let column = self.source[line_start..offset].chars().count() + 1;
+        SpadeError::ScanError { message, line, column }
+    }
+
+    // An error at the start of the current token.
+    fn error(&self, message: String) -> SpadeError {
+        self.error_at(self.start, self.start_line, message)
     }
 
     fn is_at_end(&self) -> bool {
-        return self.current >= self.source.len();
+        self.current >= self.source.len()
     }
 
 
     pub fn get_token(&self, token_type: TokenType, literal: Option<Literal>) -> Token {
         let lexeme = &self.source[self.start..self.current];
-        return Token {
+        Token {
             token_type,
             lexeme: lexeme.to_string(),
-            literal: literal,
+            literal,
             line: self.line,
-        };
+            column: self.start_column,
+        }
     }
 
     pub fn get_token_simple(&self, token_type: TokenType) -> Token {
         self.get_token(token_type, None)
     }
 
-    fn advance(&mut self) -> char {
-        // panics if self.current >= len(self.source)
-        let next_token = self.source.chars().nth(self.current).unwrap();
-        self.current += 1;
-        return next_token;
+    // `current` is a byte offset, so every step advances by the character's
+    // UTF-8 length to stay on a char boundary. At the end of the source it
+    // stays put and returns `None`.
+    fn advance(&mut self) -> Option<char> {
+        let next_char = self.peek()?;
+        self.current += next_char.len_utf8();
+        Some(next_char)
     }
 
     fn advance_if(&mut self, condition: char) -> bool {
-        if self.is_at_end() {
+        if self.peek() != Some(condition) {
             return false;
         }
-        let current_char = self.source.chars().nth(self.current).unwrap();
-        if current_char != condition {
-            return false;
-        }
-
-        self.current += 1;
-        return true;
+        self.current += condition.len_utf8();
+        true
     }
 
     fn look(&self, look_ahead: usize) -> Option<char> {
-        if self.current + look_ahead >= self.source.len() {
-            return None;
-        } 
-        Some(self.source.chars().nth(self.current + look_ahead).unwrap())
+        self.source[self.current..].chars().nth(look_ahead)
     }
 
     fn peek(&self) -> Option<char> {
         self.look(0)
     }
 
-    fn scan_string(&mut self) -> Result<Option<Token>, Error> {
+    // Decodes `\n`, `\t`, `\r`, `\\`, `\"`, `\$` and `\0`; any other escape is an
+    // error. A `${` starts an embedded expression unless its `$` is escaped.
+    fn scan_string(&mut self) -> Result<Option<Token>, SpadeError> {
+        let mut parts = vec![];
+        let mut value = String::new();
         while let Some(t) = self.peek() {
             if t == '"' {
                 break;
             }
+            if t == '$' && self.look(1) == Some('{') {
+                self.advance();
+                self.advance();
+                if !value.is_empty() {
+                    parts.push(StringPart::Text(std::mem::take(&mut value)));
+                }
+                parts.push(StringPart::Code(self.scan_interpolation()?));
+                continue;
+            }
             if t == '\n' {
                 self.line += 1;
             }
             self.advance();
+            if t != '\\' {
+                value.push(t);
+                continue;
+            }
+            let escaped = match self.peek() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('\\') => '\\',
+                Some('"') => '"',
+                Some('$') => '$',
+                Some('0') => '\0',
+                Some(c) => {
+                    let backslash = self.current - 1;
+                    return Err(self.error_at(backslash, self.line, format!("Unknown escape sequence '\\{}'", c)));
+                },
+                None => break,
+            };
+            self.advance();
+            value.push(escaped);
         }
         if self.is_at_end() {
-            return Err(anyhow::anyhow!("Unterminated string"));
+            return Err(self.error("Unterminated string".to_string()));
         }
-        println!("string:{}", self.source[self.start + 1..self.current].to_string());
-        let literal = Some(Literal::String(
-            self.source[self.start + 1..self.current].to_string()
-        ));
+        let literal = if parts.is_empty() {
+            Literal::String(value)
+        } else {
+            if !value.is_empty() {
+                parts.push(StringPart::Text(value));
+            }
+            Literal::Interpolation(parts.into_boxed_slice())
+        };
         self.advance();
-        Ok(Some(self.get_token(TokenType::String, literal)))
+        Ok(Some(self.get_token(TokenType::String, Some(literal))))
+    }
+
+    // Scans the tokens of an embedded expression whose `${` has been
+    // consumed, up to and including the `}` that balances it. Afterwards the
+    // scanner is back on the enclosing string token.
+    fn scan_interpolation(&mut self) -> Result<Vec<Token>, SpadeError> {
+        if self.interpolation_depth >= MAX_INTERPOLATION_DEPTH {
+            return Err(self.error("Too much nesting in string interpolation".to_string()));
+        }
+        let string_start = (self.start, self.start_line, self.start_column);
+        self.interpolation_depth += 1;
+        let mut tokens = vec![];
+        let mut depth = 0;
+        let result = loop {
+            if self.is_at_end() {
+                let (start, start_line, _) = string_start;
+                break Err(self.error_at(start, start_line, "Unterminated string interpolation".to_string()));
+            }
+            match self.next_token() {
+                Ok(Some(token)) if token.token_type == TokenType::RightBrace && depth == 0 => break Ok(tokens),
+                Ok(Some(token)) => {
+                    match token.token_type {
+                        TokenType::LeftBrace => depth += 1,
+                        TokenType::RightBrace => depth -= 1,
+                        _ => {},
+                    }
+                    tokens.push(token);
+                },
+                Ok(None) => {},
+                Err(e) => break Err(e),
+            }
+        };
+        self.interpolation_depth -= 1;
+        (self.start, self.start_line, self.start_column) = string_start;
+        result
+    }
+
+    // Skips the rest of a `/* ... */` comment whose opening `/*` has been
+    // consumed. Comments nest, so `/* a /* b */ c */` is a single comment.
+    fn skip_block_comment(&mut self) -> Result<(), SpadeError> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.peek() {
+                None => return Err(self.error("Unterminated block comment".to_string())),
+                Some('*') if self.look(1) == Some('/') => {
+                    self.advance();
+                    self.advance();
+                    depth -= 1;
+                },
+                Some('/') if self.look(1) == Some('*') => {
+                    self.advance();
+                    self.advance();
+                    depth += 1;
+                },
+                Some(c) => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    self.advance();
+                },
+            }
+        }
+        Ok(())
     }
 
     /**
      * Either standard identifier or reserved identifier.
      */
     fn scan_identifier(&mut self) -> Token {
-        println!("scanning identifier");
         while let Some(c) = self.peek() {
             if is_alphanumeric(c) {
                 self.advance();
@@ -217,35 +386,77 @@ impl Scanner {
         if let Some(reserved_token) = match_reserved(lexeme) {
             return self.get_token_simple(reserved_token);
         }
-        println!("scanned {}", lexeme);
         self.get_token_simple(TokenType::Identifier)
     }
 
 
     // how does the scanner work?
     // we have a thing that keeps track of the next character.
-    fn scan_token(&mut self) -> Result<Option<Token>, Error> {
+    fn scan_token(&mut self) -> Result<Option<Token>, SpadeError> {
         // ( ) { } , . - + : * then we will add it.
-        let c = self.advance();
+        let Some(c) = self.advance() else {
+            return Ok(None);
+        };
         let next_token = match c {
             '(' => Some(self.get_token_simple(TokenType::LeftParen)),
             ')' => Some(self.get_token_simple(TokenType::RightParen)),
             '{' => Some(self.get_token_simple(TokenType::LeftBrace)),
             '}' => Some(self.get_token_simple(TokenType::RightBrace)),
+            '[' => Some(self.get_token_simple(TokenType::LeftBracket)),
+            ']' => Some(self.get_token_simple(TokenType::RightBracket)),
             ',' => Some(self.get_token_simple(TokenType::Comma)),
-            '.' => Some(self.get_token_simple(TokenType::Dot)),
-            '-' => Some(self.get_token_simple(TokenType::Minus)),
-            '+' => Some(self.get_token_simple(TokenType::Plus)),
+            '.' => {
+                if self.peek() == Some('.') && self.look(1) == Some('.') {
+                    self.advance();
+                    self.advance();
+                    Some(self.get_token_simple(TokenType::Ellipsis))
+                } else if self.advance_if('.') {
+                    Some(self.get_token_simple(TokenType::DotDot))
+                } else {
+                    Some(self.get_token_simple(TokenType::Dot))
+                }
+            },
+            '-' => {
+                let token_type = ternary!(self.advance_if('='), TokenType::MinusEqual, TokenType::Minus);
+                Some(self.get_token_simple(token_type))
+            },
+            '+' => {
+                let token_type = ternary!(self.advance_if('='), TokenType::PlusEqual, TokenType::Plus);
+                Some(self.get_token_simple(token_type))
+            },
             ';' => Some(self.get_token_simple(TokenType::Semicolon)),
-            '*' => Some(self.get_token_simple(TokenType::Star)),
+            ':' => Some(self.get_token_simple(TokenType::Colon)),
+            '*' => {
+                let token_type = if self.advance_if('*') {
+                    TokenType::StarStar
+                } else {
+                    ternary!(self.advance_if('='), TokenType::StarEqual, TokenType::Star)
+                };
+                Some(self.get_token_simple(token_type))
+            },
             '!' => {
                 let token_type = ternary!(self.advance_if('='), TokenType::BangEqual, TokenType::Bang);
                 Some(self.get_token_simple(token_type))
             },
             '=' => {
-                let token_type = ternary!(self.advance_if('='), TokenType::EqualEqual, TokenType::Equal);
+                let token_type = if self.advance_if('=') {
+                    TokenType::EqualEqual
+                } else {
+                    ternary!(self.advance_if('>'), TokenType::FatArrow, TokenType::Equal)
+                };
                 Some(self.get_token_simple(token_type))
             },
+            '?' if self.peek() == Some('?') && self.look(1) == Some('=') => {
+                self.advance();
+                self.advance();
+                Some(self.get_token_simple(TokenType::QuestionQuestionEqual))
+            },
+            '?' => Some(self.get_token_simple(TokenType::Question)),
+            '|' if self.peek() == Some('|') && self.look(1) == Some('=') => {
+                self.advance();
+                self.advance();
+                Some(self.get_token_simple(TokenType::PipePipeEqual))
+            },
             '<' => {
                 let token_type = ternary!(self.advance_if('='), TokenType::LessEqual, TokenType::Less);
                 Some(self.get_token_simple(token_type))
@@ -256,12 +467,16 @@ impl Scanner {
             },
             '/' => {
                 if self.advance_if('/') {
-                    while !self.peek().is_none() && self.peek() != Some('\n') {
+                    while self.peek().is_some() && self.peek() != Some('\n') {
                         self.advance();
                     }
                     None
+                } else if self.advance_if('*') {
+                    self.skip_block_comment()?;
+                    None
                 } else {
-                    Some(self.get_token_simple(TokenType::Slash))
+                    let token_type = ternary!(self.advance_if('='), TokenType::SlashEqual, TokenType::Slash);
+                    Some(self.get_token_simple(token_type))
                 }
             },
             ' ' | '\r' | '\t' => None ,
@@ -274,24 +489,23 @@ impl Scanner {
             },
             _ => {
                 if is_digit(c) {
-                    return Ok(self.scan_number());
+                    return self.scan_number().map(Some);
                 }
                 if is_alpha(c) {
                     return Ok(Some(self.scan_identifier()));
                 }
-                return Err(anyhow::anyhow!("Unexpected character: {}", c))
+                return Err(self.error(format!("Unexpected character '{}'", c)))
             }
         };
-        return Ok(next_token);
+        Ok(next_token)
     }
 
     // Go through the source and scan it one by one.
-    fn scan_tokens(&mut self) -> Result<Vec<Token>, Error> {
+    fn scan_tokens(&mut self) -> Result<Vec<Token>, SpadeError> {
         let mut tokens = vec![];
 
         while !self.is_at_end() {
-            self.start = self.current;
-            match self.scan_token() {
+            match self.next_token() {
                 Ok(token) => {
                     if let Some(t) = token {
                         tokens.push(t)
@@ -303,44 +517,100 @@ impl Scanner {
         Ok(tokens)
     }
 
-    // scan number and pro
-    fn scan_number(&mut self) -> Option<Token> {
+    // Scans the token starting at `current`, or `None` for whitespace and
+    // comments.
+    fn next_token(&mut self) -> Result<Option<Token>, SpadeError> {
+        // Move the start column past the previous token, restarting the
+        // count if it spanned a newline.
+        let consumed = &self.source[self.start..self.current];
+        self.start_column = match consumed.rfind('\n') {
+            Some(newline) => consumed[newline + 1..].chars().count() + 1,
+            None => self.start_column + consumed.chars().count(),
+        };
+        self.start = self.current;
+        self.start_line = self.line;
+        self.scan_token()
+    }
+
+    // Consumes a run of digits, allowing single `_` separators between them
+    // as in `1_000`. An underscore that isn't followed by a digit is left
+    // for the caller to reject.
+    fn scan_digits(&mut self) {
         while let Some(c) = self.peek() {
-            if !is_digit(c) {
+            if is_digit(c) || (c == '_' && self.look(1).is_some_and(is_digit)) {
+                self.advance();
+            } else {
                 break;
             }
-            self.advance();
         }
+    }
+
+    // `scan_number` only collects lexemes f64 can parse, but a scanner bug or
+    // a future syntax change shouldn't be able to panic the interpreter.
+    fn parse_float(&self, digits: &str) -> Result<f64, SpadeError> {
+        digits.parse::<f64>().map_err(|_| self.error(format!("Invalid number literal '{}'", digits)))
+    }
+
+    // scan number and pro
+    fn scan_number(&mut self) -> Result<Token, SpadeError> {
+        self.scan_digits();
+
+        // A literal with a fraction or an exponent is a float; otherwise it is
+        // an integer.
+        let mut float = false;
 
         // If the '.' is valid, we continue to decode it.
-        if self.peek() == Some('.') {
-            if let Some(c) = self.look(1) {
-                if is_digit(c) {
-                    self.advance();
-                    while let Some(c) = self.peek() {
-                        if !is_digit(c) {
-                            break;
-                        }
-                        self.advance();
-                    }
-                }
+        if self.peek() == Some('.') && self.look(1).is_some_and(is_digit) {
+            float = true;
+            self.advance();
+            self.scan_digits();
+        }
+
+        // An exponent needs at least one digit, so in `2e` or `2e+` the `e`
+        // is left to be rejected as a suffix.
+        let exponent_digit = match self.look(1) {
+            Some('+') | Some('-') => 2,
+            _ => 1,
+        };
+        if matches!(self.peek(), Some('e') | Some('E')) && self.look(exponent_digit).is_some_and(is_digit) {
+            float = true;
+            for _ in 0..exponent_digit {
+                self.advance();
             }
+            self.scan_digits();
         }
 
-        Some(self.get_token(TokenType::Number, Some(Literal::Number(
-            self.source[self.start..self.current].parse::<f64>().unwrap()
-        ))))
+        let digits = self.source[self.start..self.current].replace('_', "");
+
+        // An optional suffix forces the type: `10f` is a float, and `10i` is
+        // an integer like `10`.
+        let suffix_start = self.current;
+        while self.peek().is_some_and(is_alphanumeric) {
+            self.advance();
+        }
+        if self.source[suffix_start..].starts_with('_') {
+            let lexeme = &self.source[self.start..self.current];
+            return Err(self.error(format!("Misplaced '_' in number literal {}", lexeme)));
+        }
+        let literal = match &self.source[suffix_start..self.current] {
+            "" if float => Literal::Number(self.parse_float(&digits)?),
+            "f" => Literal::Number(self.parse_float(&digits)?),
+            suffix @ ("" | "i") => match digits.parse::<i64>() {
+                Ok(n) => Literal::Integer(n),
+                Err(_) => return Err(self.error(format!("Invalid integer literal {}{}", digits, suffix))),
+            },
+            suffix => return Err(self.error(format!("Invalid number suffix '{}' on {}", suffix, digits))),
+        };
+        Ok(self.get_token(TokenType::Number, Some(literal)))
     }
 }
 
-pub fn scan_tokens(source: String) -> Result<Vec<Token>, Error> {
-    return Scanner::new(source).scan_tokens();
+pub fn scan_tokens(source: String) -> Result<Vec<Token>, SpadeError> {
+    Scanner::new(source).scan_tokens()
 }
 
 #[cfg(test)]
 mod tests {
-    use std::string;
-
     use super::*;
     
     fn match_types(tokens: Vec<Token>, types: Vec<TokenType>) {
@@ -351,12 +621,14 @@ mod tests {
 
     #[test]
     fn test_scan_simple_tokens() {
-        let source = "(){},.+-;*".to_string();
+        let source = "(){}[],.+-;*".to_string();
         let expected_types = vec![
             TokenType::LeftParen,
             TokenType::RightParen,
             TokenType::LeftBrace,
             TokenType::RightBrace,
+            TokenType::LeftBracket,
+            TokenType::RightBracket,
             TokenType::Comma,
             TokenType::Dot,
             TokenType::Plus,
@@ -370,12 +642,13 @@ mod tests {
 
     #[test]
     fn test_multiple_tokens() {
-        let source = "! != == = > >= < <=".to_string();
+        let source = "! != == = => > >= < <=".to_string();
         let expected_types = vec![
             TokenType::Bang,
             TokenType::BangEqual,
             TokenType::EqualEqual,
             TokenType::Equal,
+            TokenType::FatArrow,
             TokenType::Greater,
             TokenType::GreaterEqual,
             TokenType::Less,
@@ -385,6 +658,143 @@ mod tests {
         match_types(tokens, expected_types)
     }
 
+    #[test]
+    fn test_scan_long_input() {
+        // Positions are byte offsets into the source, so each step is O(1)
+        // and scanning stays linear in the input size.
+        let source = "let x = \"café\" + 1.5;\n".repeat(20_000);
+        let tokens = scan_tokens(source).unwrap();
+        assert_eq!(tokens.len(), 20_000 * 7);
+        assert_eq!(tokens.last().unwrap().line, 20_000);
+    }
+
+    #[test]
+    fn test_multi_byte_characters() {
+        let tokens = scan_tokens("\"café\" ; \"日本\"".to_string()).unwrap();
+        match_types(tokens.clone(), vec![TokenType::String, TokenType::Semicolon, TokenType::String]);
+        assert_eq!(tokens[0].literal, Some(Literal::String("café".to_string())));
+        assert_eq!(tokens[0].lexeme, "\"café\"");
+        assert_eq!(tokens[2].literal, Some(Literal::String("日本".to_string())));
+        assert!(scan_tokens("é".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let string = |source: &str| match scan_tokens(source.to_string()).unwrap()[0].literal.clone() {
+            Some(Literal::String(s)) => s,
+            other => panic!("expected a string literal, got {:?}", other),
+        };
+        assert_eq!(string(r#""a\tb""#), "a\tb");
+        assert_eq!(string(r#""\\""#), "\\");
+        assert_eq!(string(r#""line1\nline2\r\0""#), "line1\nline2\r\0");
+        assert_eq!(string(r#""say \"hi\"""#), "say \"hi\"");
+        assert_eq!(string(r#""cost: \${x} or $5""#), "cost: ${x} or $5");
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let tokens = scan_tokens("\"x=${a + 1}!\" y".to_string()).unwrap();
+        assert_eq!(tokens.len(), 2);
+        let Some(Literal::Interpolation(parts)) = &tokens[0].literal else { panic!("{:?}", tokens[0]) };
+        let StringPart::Code(code) = &parts[1] else { panic!("{:?}", parts) };
+        assert_eq!(parts[0], StringPart::Text("x=".to_string()));
+        assert_eq!(code.iter().map(|t| t.lexeme.as_str()).collect::<Vec<&str>>(), ["a", "+", "1"]);
+        assert_eq!(code[2].column, 10);
+        assert_eq!(parts[2], StringPart::Text("!".to_string()));
+        assert_eq!(tokens[1].column, 15);
+
+        // Braces and strings inside the expression don't end it early.
+        let tokens = scan_tokens(r#""${ {"k": "}"}["k"] }""#.to_string()).unwrap();
+        let Some(Literal::Interpolation(parts)) = &tokens[0].literal else { panic!("{:?}", tokens[0]) };
+        assert_eq!(parts.len(), 1);
+
+        let error = scan_tokens("x;\n  \"a ${1 + 2".to_string()).unwrap_err();
+        assert_eq!(error.to_string(), "Unterminated string interpolation [line 2, column 3]");
+        // The quote meant to close the string opens one inside the expression.
+        assert!(scan_tokens("\"a ${1 + 2\"".to_string()).is_err());
+        let error = scan_tokens("\"${".repeat(100)).unwrap_err();
+        assert!(error.to_string().starts_with("Too much nesting in string interpolation"), "{}", error);
+    }
+
+    #[test]
+    fn test_unknown_string_escape() {
+        let error = scan_tokens("x;\n\"bad \\q\"".to_string()).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown escape sequence '\\q' [line 2, column 6]");
+        assert!(scan_tokens(r#""trailing \"#.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_errors_report_location() {
+        let error = scan_tokens("let a = 1;\nlet b = 2;\nlet s = \"open;\n".to_string()).unwrap_err();
+        assert_eq!(error.to_string(), "Unterminated string [line 3, column 9]");
+        let error = scan_tokens("x;\n  café # 1".to_string()).unwrap_err();
+        assert_eq!(error.to_string(), "Unexpected character 'é' [line 2, column 6]");
+        let error = scan_tokens("\n\n 10x".to_string()).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid number suffix 'x' on 10 [line 3, column 2]");
+    }
+
+    #[test]
+    fn test_token_columns() {
+        let tokens = scan_tokens("let a = 1;\n  print \"é\" + b;".to_string()).unwrap();
+        let positions: Vec<(usize, usize)> = tokens.iter().map(|t| (t.line, t.column)).collect();
+        assert_eq!(positions, [(1, 1), (1, 5), (1, 7), (1, 9), (1, 10), (2, 3), (2, 9), (2, 13), (2, 15), (2, 16)]);
+
+        // A token after a multi-line comment counts from the comment's last line.
+        let tokens = scan_tokens("/* a\n  b */ x y".to_string()).unwrap();
+        assert_eq!((tokens[1].line, tokens[1].column), (2, 10));
+    }
+
+    #[test]
+    fn test_block_comment() {
+        let tokens = scan_tokens("1 /* spans\ntwo lines */ + 2".to_string()).unwrap();
+        match_types(tokens.clone(), vec![TokenType::Number, TokenType::Plus, TokenType::Number, TokenType::EOF]);
+        assert_eq!(tokens[1].line, 2);
+
+        let tokens = scan_tokens("/* outer /* inner */ still outer */ x".to_string()).unwrap();
+        match_types(tokens, vec![TokenType::Identifier, TokenType::EOF]);
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let error = scan_tokens("x;\n/* never\nclosed".to_string()).unwrap_err();
+        assert_eq!(error.to_string(), "Unterminated block comment [line 2, column 1]");
+        assert!(scan_tokens("/* /* */".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_compound_assignment_operators() {
+        let tokens = scan_tokens("a += 1; b -= 2; c *= 3; d /= 4; e ** 2;".to_string()).unwrap();
+        let operators: Vec<TokenType> = tokens.iter().skip(1).step_by(4).map(|t| t.token_type).collect();
+        assert_eq!(operators, [TokenType::PlusEqual, TokenType::MinusEqual, TokenType::StarEqual, TokenType::SlashEqual, TokenType::StarStar]);
+    }
+
+    #[test]
+    fn test_conditional_assignment_operators() {
+        let tokens = scan_tokens("x ??= 1; y ||= 2;".to_string()).unwrap();
+        assert_eq!(tokens[1].token_type, TokenType::QuestionQuestionEqual);
+        assert_eq!(tokens[5].token_type, TokenType::PipePipeEqual);
+        let tokens = scan_tokens("x ?= 1".to_string()).unwrap();
+        match_types(tokens, vec![TokenType::Identifier, TokenType::Question, TokenType::Equal, TokenType::Number]);
+        assert!(scan_tokens("x | y".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_ellipsis() {
+        let source = "f(...xs) a.b".to_string();
+        let expected_types = vec![
+            TokenType::Identifier,
+            TokenType::LeftParen,
+            TokenType::Ellipsis,
+            TokenType::Identifier,
+            TokenType::RightParen,
+            TokenType::Identifier,
+            TokenType::Dot,
+            TokenType::Identifier,
+        ];
+        let tokens = scan_tokens(source).unwrap();
+        match_types(tokens, expected_types)
+    }
+
     #[test]
     fn test_string() {
         let source = "\"hello world\"".to_string();
@@ -400,10 +810,61 @@ mod tests {
         assert_eq!(string_token.literal, Some(Literal::Number(34.33f64)));
     }
 
+    #[test]
+    fn test_number_suffixes() {
+        let literal = |source: &str| scan_tokens(source.to_string()).unwrap()[0].literal.clone();
+        assert_eq!(literal("10i"), Some(Literal::Integer(10)));
+        assert_eq!(literal("10f"), Some(Literal::Number(10.0)));
+        assert_eq!(literal("2.5f"), Some(Literal::Number(2.5)));
+        assert!(scan_tokens("10x".to_string()).is_err());
+        assert!(scan_tokens("2.5i".to_string()).is_err());
+        assert!(scan_tokens("99999999999999999999i".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_scientific_notation() {
+        let literal = |source: &str| scan_tokens(source.to_string()).unwrap()[0].literal.clone();
+        assert_eq!(literal("1e3"), Some(Literal::Number(1000.0)));
+        assert_eq!(literal("1.5e2"), Some(Literal::Number(150.0)));
+        assert_eq!(literal("2E-2"), Some(Literal::Number(0.02)));
+        assert_eq!(literal("2.5e+1f"), Some(Literal::Number(25.0)));
+        assert!(scan_tokens("2e".to_string()).is_err());
+        assert!(scan_tokens("2e+".to_string()).is_err());
+        assert!(scan_tokens("1e3i".to_string()).is_err());
+        // `2.e3` is not a number with an exponent: the `.` isn't followed by a digit.
+        let tokens = scan_tokens("2.e3".to_string()).unwrap();
+        match_types(tokens, vec![TokenType::Number, TokenType::Dot, TokenType::Identifier]);
+    }
+
+    #[test]
+    fn test_numeric_separators() {
+        let literal = |source: &str| scan_tokens(source.to_string()).unwrap()[0].literal.clone();
+        assert_eq!(literal("1_000"), Some(Literal::Integer(1000)));
+        assert_eq!(literal("1_000_000i"), Some(Literal::Integer(1_000_000)));
+        assert_eq!(literal("2.500_5"), Some(Literal::Number(2.5005)));
+        assert_eq!(literal("1e1_0"), Some(Literal::Number(1e10)));
+        for source in ["1__0", "1_", "1_.5", "1.5_"] {
+            assert!(scan_tokens(source.to_string()).is_err(), "{} should not scan", source);
+        }
+        assert_eq!(scan_tokens("1__0".to_string()).unwrap_err().to_string(), "Misplaced '_' in number literal 1__0 [line 1, column 1]");
+        // A leading underscore makes an identifier, not a number.
+        match_types(scan_tokens("_1".to_string()).unwrap(), vec![TokenType::Identifier]);
+    }
+
+    #[test]
+    fn test_unparseable_number_is_an_error() {
+        let scanner = Scanner::new("x;\n  1.2.3".to_string());
+        assert_eq!(scanner.parse_float("1.5").unwrap(), 1.5);
+        let scanner = Scanner { start: 5, start_line: 2, ..scanner };
+        let error = scanner.parse_float("1.2.3").unwrap_err();
+        assert_eq!(error.to_string(), "Invalid number literal '1.2.3' [line 2, column 3]");
+    }
+
     #[test]
     fn test_arithmetic() {
         let source: String = "3+4".to_string();
-        let string_token = &scan_tokens(source).unwrap();
+        let tokens = scan_tokens(source).unwrap();
+        match_types(tokens, vec![TokenType::Number, TokenType::Plus, TokenType::Number]);
     }
 
     #[test]
@@ -1,13 +1,21 @@
-use core::error;
 
-use anyhow::Result;
+use std::rc::Rc;
 
-use crate::token::{self, Token, TokenType};
-use crate::expressions::{BinaryOp, Expr, Literal, Statement, UnaryOp};
+use crate::error::SpadeError;
+use crate::token::{StringPart, Token, TokenType};
+use crate::expressions::{BinaryOp, Expr, Line, Literal, MatchArm, Resolution, Statement, UnaryOp};
+
+// Deeper nesting than this is rejected rather than risking a native stack
+// overflow on adversarial input.
+const MAX_NESTING: usize = 64;
 
 struct Parser {
     tokens: Vec<Token>,
-    current: usize
+    current: usize,
+    depth: usize,
+    // Number of enclosing loops within the current function body, so `break`
+    // and `continue` outside a loop are rejected.
+    loop_depth: usize,
 }
 
 impl Parser {
@@ -15,7 +23,25 @@ impl Parser {
         Parser {
             tokens,
             current: 0,
+            depth: 0,
+            loop_depth: 0,
+        }
+    }
+
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, SpadeError>) -> Result<T, SpadeError> {
+        if self.depth >= MAX_NESTING {
+            return Err(self.error("Too much nesting".to_string()));
         }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    // A parse error at the token the parser stopped on.
+    fn error(&self, message: String) -> SpadeError {
+        let (line, column) = self.tokens.get(self.current).or(self.tokens.last()).map_or((1, 1), |token| (token.line, token.column));
+        SpadeError::ParseError { message, line, column }
     }
 
     fn is_at_end(&self) -> bool {
@@ -45,6 +71,10 @@ impl Parser {
         }
     }
 
+    fn check_next(&self, token_type: TokenType) -> bool {
+        self.tokens.get(self.current + 1).is_some_and(|token| token.token_type == token_type)
+    }
+
     fn match_token(&mut self, types: &[TokenType]) -> bool {
         for token_type in types {
             if self.check(*token_type) {
@@ -55,62 +85,72 @@ impl Parser {
         false
     }
 
-    fn consume(&mut self, types: &[TokenType], error_message: String) -> Result<Token, String>{
+    fn consume(&mut self, types: &[TokenType], error_message: String) -> Result<Token, SpadeError>{
         if !self.match_token(types) {
-            return Err(error_message);
+            return Err(self.error(error_message));
         }
         Ok(self.previous().clone())
     }
 
-    fn print_statement(&mut self) -> Result<Statement, String> {
-        let value = match self.expression() {
-            Ok(val) => val,
-            Err(e) => return Err(e)
-        };
-        if let Err(error) = self.consume(&[TokenType::Semicolon], "Expect ';' after value.".to_string()) {
-            return Err(error);
+    fn print_statement(&mut self) -> Result<Statement, SpadeError> {
+        let mut values = vec![self.expression()?];
+        while self.match_token(&[TokenType::Comma]) {
+            values.push(self.expression()?);
         }
-        Ok(Statement::Print(value))
+        self.consume(&[TokenType::Semicolon], "Expect ';' after value.".to_string())?;
+        Ok(Statement::Print(values))
     }
 
-    fn expression_statement(&mut self) -> Result<Statement, String> {
-        let value = match self.expression() {
-            Ok(val) => val,
-            Err(e) => return Err(e)
-        };
-        if let Err(error) = self.consume(&[TokenType::Semicolon], "Expect ';' after value.".to_string()) {
-            return Err(error);
-        }
+    fn expression_statement(&mut self) -> Result<Statement, SpadeError> {
+        let value = self.expression()?;
+        self.consume(&[TokenType::Semicolon], "Expect ';' after value.".to_string())?;
         Ok(Statement::Expression(value))
     }
 
-    fn var_declaration(&mut self) -> Result<Statement, String> {
+    fn var_declaration(&mut self) -> Result<Statement, SpadeError> {
+        let mutable = self.match_token(&[TokenType::Mut]);
         let name = self.consume(&[TokenType::Identifier], "'let' assignment must be provided a name".to_string())?;
-        self.consume(&[TokenType::Equal], "'let' assignment must be followed by '='".to_string())?;
-
-        if self.match_token(&[TokenType::Semicolon]) {
+        // `let x;` declares `x` as nil, as does the older `let x = ;`.
+        let has_equal = !self.match_token(&[TokenType::Semicolon]);
+        if has_equal {
+            self.consume(&[TokenType::Equal], "'let' assignment must be followed by '=' or ';'".to_string())?;
+        }
+        if !has_equal || self.match_token(&[TokenType::Semicolon]) {
             return Ok(Statement::VarDec { 
                 name: name.lexeme, 
-                initializer: None
+                initializer: None,
+                mutable,
             })
         }
 
         let expr = self.expression()?;
+
+        // `let x = e1 in e2;` at the start of a statement is a let-expression.
+        if self.match_token(&[TokenType::In]) {
+            if mutable {
+                return Err(self.error("A let-expression binding can't be 'mut'".to_string()));
+            }
+            let body = self.expression()?;
+            self.consume(&[TokenType::Semicolon], "Expect ';' after value.".to_string())?;
+            return Ok(Statement::Expression(Expr::Let { name: name.lexeme, value: Box::new(expr), body: Box::new(body) }));
+        }
+
         self.consume(&[TokenType::Semicolon], "Delaration must end with semicolon".to_string())?;
 
-        return Ok(Statement::VarDec {
+        Ok(Statement::VarDec {
             name: name.lexeme,
             initializer: Some(expr),
+            mutable,
         })
     }
 
-    fn block_statement(&mut self) -> Result<Statement, String> {
+    fn block_statement(&mut self) -> Result<Statement, SpadeError> {
         let statements = self.block()?;
         self.consume(&[TokenType::RightBrace], "Expect '}' after block".to_string())?;
         Ok(Statement::Block(statements))
     }
 
-    fn if_statement(&mut self) -> Result<Statement, String> {
+    fn if_statement(&mut self) -> Result<Statement, SpadeError> {
         self.consume(&[TokenType::LeftParen], "Expect '(' after 'if'".to_string())?;
         let condition = self.expression()?;
         self.consume(&[TokenType::RightParen], "Expect ')' after condition".to_string())?;
@@ -123,10 +163,166 @@ impl Parser {
         Ok(Statement::If { condition, then_branch, else_branch })
     }
 
-    fn fn_statement(&mut self) -> Result<Statement, String> {
+    // `unless (cond) a else b` is sugar for `if (!cond) a else b`.
+    fn unless_statement(&mut self) -> Result<Statement, SpadeError> {
+        let line = Line(self.previous().line);
+        self.consume(&[TokenType::LeftParen], "Expect '(' after 'unless'".to_string())?;
+        let condition = self.expression()?;
+        self.consume(&[TokenType::RightParen], "Expect ')' after condition".to_string())?;
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.match_token(&[TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+        let condition = Expr::Unary { op: UnaryOp::Not, expr: Box::new(condition), line };
+        Ok(Statement::If { condition, then_branch, else_branch })
+    }
+
+    fn loop_body(&mut self) -> Result<Statement, SpadeError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn while_statement(&mut self) -> Result<Statement, SpadeError> {
+        self.consume(&[TokenType::LeftParen], "Expect '(' after 'while'".to_string())?;
+        let condition = self.expression()?;
+        self.consume(&[TokenType::RightParen], "Expect ')' after condition".to_string())?;
+        let body = Box::new(self.loop_body()?);
+        Ok(Statement::While { condition, body, increment: None })
+    }
+
+    fn loop_control(&mut self, statement: Statement, keyword: &str) -> Result<Statement, SpadeError> {
+        if self.loop_depth == 0 {
+            let token = self.previous();
+            return Err(SpadeError::ParseError {
+                message: format!("'{}' outside of a loop", keyword),
+                line: token.line,
+                column: token.column,
+            });
+        }
+        self.consume(&[TokenType::Semicolon], format!("Expect ';' after '{}'", keyword))?;
+        Ok(statement)
+    }
+
+    // `for (init; cond; incr) body` desugars into `{ init; while (cond) body }`
+    // with `incr` as the loop's increment, so the loop variable is scoped to
+    // the loop and `continue` still runs the increment. A missing condition
+    // loops forever.
+    fn for_statement(&mut self) -> Result<Statement, SpadeError> {
+        self.consume(&[TokenType::LeftParen], "Expect '(' after 'for'".to_string())?;
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::In) {
+            return self.for_in_statement();
+        }
+        let initializer = if self.match_token(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_token(&[TokenType::Let]) {
+            // The loop variable is updated by the increment, so it is always
+            // mutable.
+            match self.var_declaration()? {
+                Statement::VarDec { name, initializer, .. } => Some(Statement::VarDec { name, initializer, mutable: true }),
+                _ => unreachable!(),
+            }
+        } else {
+            Some(self.expression_statement()?)
+        };
+        let condition = if self.check(TokenType::Semicolon) {
+            Expr::Literal(Literal::Bool(true))
+        } else {
+            self.expression()?
+        };
+        self.consume(&[TokenType::Semicolon], "Expect ';' after loop condition".to_string())?;
+        let increment = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(&[TokenType::RightParen], "Expect ')' after for clauses".to_string())?;
+
+        let body = Box::new(self.loop_body()?);
+        let while_loop = Statement::While { condition, body, increment };
+        match initializer {
+            Some(initializer) => Ok(Statement::Block(vec![initializer, while_loop])),
+            None => Ok(while_loop),
+        }
+    }
+
+    // `for (name in iterable) body`, after the opening parenthesis.
+    fn for_in_statement(&mut self) -> Result<Statement, SpadeError> {
+        let name = self.advance().lexeme.clone();
+        let line = Line(self.advance().line);
+        let iterable = self.expression()?;
+        self.consume(&[TokenType::RightParen], "Expect ')' after for-in iterable".to_string())?;
+        let body = Box::new(self.loop_body()?);
+        Ok(Statement::ForIn { name, iterable, body, line })
+    }
+
+    fn import_statement(&mut self) -> Result<Statement, SpadeError> {
+        let line = Line(self.previous().line);
+        let path = match &self.consume(&[TokenType::String], "Expect file path after 'import'".to_string())?.literal {
+            Some(crate::token::Literal::String(path)) => path.clone(),
+            _ => return Err(self.error("String token without string literal".to_string())),
+        };
+        self.consume(&[TokenType::Semicolon], "Expect ';' after import path".to_string())?;
+        Ok(Statement::Import { path, line })
+    }
+
+    fn match_statement(&mut self) -> Result<Statement, SpadeError> {
+        self.consume(&[TokenType::LeftParen], "Expect '(' after 'match'".to_string())?;
+        let subject = self.expression()?;
+        self.consume(&[TokenType::RightParen], "Expect ')' after match subject".to_string())?;
+        self.consume(&[TokenType::LeftBrace], "Expect '{' before match arms".to_string())?;
+        let mut arms = vec![];
+        while !self.is_at_end() && !self.check(TokenType::RightBrace) {
+            arms.push(self.match_arm()?);
+        }
+        self.consume(&[TokenType::RightBrace], "Expect '}' after match arms".to_string())?;
+        Ok(Statement::Match { subject, arms })
+    }
+
+    fn match_arm(&mut self) -> Result<MatchArm, SpadeError> {
+        let pattern = if self.check(TokenType::Identifier) && self.peek().lexeme == "_" {
+            self.advance();
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let guard = if self.match_token(&[TokenType::If]) { Some(self.expression()?) } else { None };
+        self.consume(&[TokenType::FatArrow], "Expect '=>' after match pattern".to_string())?;
+        let body = self.statement()?;
+        Ok(MatchArm { pattern, guard, body })
+    }
+
+    fn with_statement(&mut self) -> Result<Statement, SpadeError> {
+        self.consume(&[TokenType::LeftParen], "Expect '(' after 'with'".to_string())?;
+        let name = self.consume(&[TokenType::Identifier], "Expect resource name".to_string())?;
+        self.consume(&[TokenType::Equal], "Expect '=' after resource name".to_string())?;
+        let resource = self.expression()?;
+        self.consume(&[TokenType::RightParen], "Expect ')' after resource".to_string())?;
+        let body = Box::new(self.statement()?);
+        Ok(Statement::With { name: name.lexeme, resource, body })
+    }
+
+    fn fn_statement(&mut self) -> Result<Statement, SpadeError> {
         let name = self.consume(&[TokenType::Identifier], "Expect function name".to_string())?;
         self.consume(&[TokenType::LeftParen], "Expect '(' after function name".to_string())?;
+        let (parameters, body) = self.function_rest()?;
+        Ok(Statement::Fn { name: name.lexeme, parameters, body: Rc::new(body) })
+    }
+
+    // Kept out of `primary` so that deeply nested expressions don't pay for
+    // its stack space at every level.
+    fn function_expression(&mut self) -> Result<Expr, SpadeError> {
+        self.consume(&[TokenType::LeftParen], "Expect '(' after 'fn'".to_string())?;
+        let (parameters, body) = self.function_rest()?;
+        Ok(Expr::Function { parameters, body: Rc::new(body) })
+    }
 
+    // The parameter list and body of a named or anonymous function, after
+    // its opening '('.
+    fn function_rest(&mut self) -> Result<(Vec<String>, Statement), SpadeError> {
         let mut parameters: Vec<String> = vec![];
         while !self.is_at_end() && !self.check(TokenType::RightParen) {
             let parameter = self.consume(&[TokenType::Identifier], "Expect parameter name".to_string())?;
@@ -136,11 +332,15 @@ impl Parser {
             }
         }
         self.consume(&[TokenType::RightParen], "Expect ')' after parameters".to_string())?;
-        let body = Box::new(self.block_statement()?);
-        Ok(Statement::Fn { name: name.lexeme, parameters, body })
+        self.consume(&[TokenType::LeftBrace], "Expect '{' before function body".to_string())?;
+        // Loops around the definition don't extend into the function body.
+        let enclosing_loops = std::mem::replace(&mut self.loop_depth, 0);
+        let body = self.block_statement();
+        self.loop_depth = enclosing_loops;
+        Ok((parameters, body?))
     }
 
-    fn return_statement(&mut self) -> Result<Statement, String> {
+    fn return_statement(&mut self) -> Result<Statement, SpadeError> {
         if self.match_token(&[TokenType::Semicolon]) {
             Ok(Statement::Return(None))
         } else {
@@ -150,7 +350,11 @@ impl Parser {
         }
     }
 
-    fn statement(&mut self) -> Result<Statement, String> {
+    fn statement(&mut self) -> Result<Statement, SpadeError> {
+        self.nested(|parser| parser.statement_kind())
+    }
+
+    fn statement_kind(&mut self) -> Result<Statement, SpadeError> {
         if self.match_token(&[TokenType::Print]) {
             return self.print_statement();
         } else if self.match_token(&[TokenType::Let]) {
@@ -159,21 +363,132 @@ impl Parser {
             return self.block_statement();
         } else if self.match_token(&[TokenType::If]) {
             return self.if_statement();
-        } else if self.match_token(&[TokenType::Fn]) {  
+        } else if self.match_token(&[TokenType::Unless]) {
+            return self.unless_statement();
+        } else if self.check(TokenType::Fn) && !self.check_next(TokenType::LeftParen) {
+            // `fn (` starts an anonymous function in an expression statement.
+            self.advance();
             return self.fn_statement();
         } else if self.match_token(&[TokenType::Return]) {
             return self.return_statement();
+        } else if self.match_token(&[TokenType::While]) {
+            return self.while_statement();
+        } else if self.match_token(&[TokenType::For]) {
+            return self.for_statement();
+        } else if self.match_token(&[TokenType::Break]) {
+            return self.loop_control(Statement::Break, "break");
+        } else if self.match_token(&[TokenType::Continue]) {
+            return self.loop_control(Statement::Continue, "continue");
+        } else if self.match_token(&[TokenType::Import]) {
+            return self.import_statement();
+        } else if self.match_token(&[TokenType::Match]) {
+            return self.match_statement();
+        } else if self.match_token(&[TokenType::With]) {
+            return self.with_statement();
         }
 
-        return self.expression_statement()
+        self.expression_statement()
+    }
+
+    fn expression(&mut self) -> Result<Expr, SpadeError> {
+        self.nested(|parser| parser.assignment())
+    }
+
+    // Assignment is right-associative and its target must be a variable.
+    // `x ??= v` and `x ||= v` desugar into conditionals that only evaluate
+    // and assign `v` when `x` is nil or falsy respectively, and `x += v`
+    // into `x = x + v` (likewise `-=`, `*=` and `/=`).
+    fn assignment(&mut self) -> Result<Expr, SpadeError> {
+        let expr = self.or()?;
+        if self.match_token(&[TokenType::Question]) {
+            return self.conditional(expr);
+        }
+        if !self.match_token(&[
+            TokenType::Equal,
+            TokenType::QuestionQuestionEqual,
+            TokenType::PipePipeEqual,
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+        ]) {
+            return Ok(expr);
+        }
+        let operator = self.previous().token_type;
+        let operator_line = self.previous().line;
+        let value = self.nested(|parser| parser.assignment())?;
+        let Expr::Literal(Literal::Var(token, _)) = expr else {
+            return Err(self.error("Invalid assignment target".to_string()));
+        };
+        let target = Box::new(Expr::Literal(Literal::Var(token.clone(), Resolution::Unresolved)));
+        let op = match operator {
+            TokenType::PlusEqual => Some(BinaryOp::Plus),
+            TokenType::MinusEqual => Some(BinaryOp::Minus),
+            TokenType::StarEqual => Some(BinaryOp::Multiply),
+            TokenType::SlashEqual => Some(BinaryOp::Divide),
+            _ => None,
+        };
+        let value = match op {
+            Some(op) => Expr::Binary { left: target.clone(), op, right: Box::new(value), line: Line(operator_line) },
+            None => value,
+        };
+        let assign = Box::new(Expr::Assign { token, value: Box::new(value), resolution: Resolution::Unresolved });
+        Ok(match operator {
+            TokenType::QuestionQuestionEqual => Expr::Conditional {
+                condition: Box::new(Expr::Binary {
+                    left: target.clone(),
+                    op: BinaryOp::EqualEqual,
+                    right: Box::new(Expr::Literal(Literal::Nil)),
+                    line: Line(operator_line),
+                }),
+                then_branch: assign,
+                else_branch: target,
+            },
+            TokenType::PipePipeEqual => Expr::Conditional {
+                condition: target.clone(),
+                then_branch: target,
+                else_branch: assign,
+            },
+            _ => *assign,
+        })
+    }
+
+    // The rest of `condition ? a : b` after the `?`. It is right-associative,
+    // so `a ? b : c ? d : e` nests in the else branch. Kept out of
+    // `assignment` so that deeply nested expressions don't pay for its stack
+    // space at every level.
+    fn conditional(&mut self, condition: Expr) -> Result<Expr, SpadeError> {
+        let then_branch = self.expression()?;
+        self.consume(&[TokenType::Colon], "Expect ':' after then branch of conditional".to_string())?;
+        let else_branch = self.nested(|parser| parser.assignment())?;
+        Ok(Expr::Conditional {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        })
+    }
+
+    fn or(&mut self) -> Result<Expr, SpadeError> {
+        let mut expr = self.and()?;
+        while self.match_token(&[TokenType::Or]) {
+            let line = Line(self.previous().line);
+            let right = self.and()?;
+            expr = Expr::Binary { left: Box::new(expr), op: BinaryOp::Or, right: Box::new(right), line };
+        }
+        Ok(expr)
     }
 
-    fn expression(&mut self) -> Result<Expr, String> {
-        self.equality()
+    fn and(&mut self) -> Result<Expr, SpadeError> {
+        let mut expr = self.equality()?;
+        while self.match_token(&[TokenType::And]) {
+            let line = Line(self.previous().line);
+            let right = self.equality()?;
+            expr = Expr::Binary { left: Box::new(expr), op: BinaryOp::And, right: Box::new(right), line };
+        }
+        Ok(expr)
     }
 
-    fn equality(&mut self) -> Result<Expr, String> {
-        println!("got to equality");
+    fn equality(&mut self) -> Result<Expr, SpadeError> {
         let mut expr = self.comparison()?;
 
         while self.match_token(&[TokenType::BangEqual, TokenType::EqualEqual]) {
@@ -182,20 +497,21 @@ impl Parser {
                 TokenType::EqualEqual => BinaryOp::EqualEqual,
                 _ => unreachable!(),
             };
+            let line = Line(self.previous().line);
             let right = self.comparison()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op: operator,
                 right: Box::new(right),
+                line,
             };
         }
 
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expr, String> {
-        println!("got to comparison");
-        let mut expr = self.term()?;
+    fn comparison(&mut self) -> Result<Expr, SpadeError> {
+        let mut expr = self.range()?;
 
         while self.match_token(&[TokenType::Greater, TokenType::GreaterEqual, TokenType::Less, TokenType::LessEqual]) {
             let operator = match self.previous().token_type {
@@ -205,19 +521,31 @@ impl Parser {
                 TokenType::LessEqual => BinaryOp::LessEqual,
                 _ => unreachable!(),
             };
-            let right = self.term()?;
+            let line = Line(self.previous().line);
+            let right = self.range()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op: operator,
                 right: Box::new(right),
+                line,
             };
         }
 
         Ok(expr)
     }
 
-    fn term(&mut self) -> Result<Expr, String> {
-        println!("got to term");
+    // `start..end` is not associative: `0..1..2` is a parse error.
+    fn range(&mut self) -> Result<Expr, SpadeError> {
+        let start = self.term()?;
+        if !self.match_token(&[TokenType::DotDot]) {
+            return Ok(start);
+        }
+        let line = Line(self.previous().line);
+        let end = self.term()?;
+        Ok(Expr::Binary { left: Box::new(start), op: BinaryOp::Range, right: Box::new(end), line })
+    }
+
+    fn term(&mut self) -> Result<Expr, SpadeError> {
         let mut expr = self.factor()?;
 
         while self.match_token(&[TokenType::Minus, TokenType::Plus]) {
@@ -226,19 +554,20 @@ impl Parser {
                 TokenType::Plus => BinaryOp::Plus,
                 _ => unreachable!(),
             };
+            let line = Line(self.previous().line);
             let right = self.factor()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op: operator,
                 right: Box::new(right),
+                line,
             };
         }
 
         Ok(expr)
     }
 
-    fn factor(&mut self) -> Result<Expr, String> {
-        println!("got to factor");
+    fn factor(&mut self) -> Result<Expr, SpadeError> {
         let mut expr = self.unary()?;
 
         while self.match_token(&[TokenType::Slash, TokenType::Star]) {
@@ -247,36 +576,81 @@ impl Parser {
                 TokenType::Star => BinaryOp::Multiply,
                 _ => unreachable!(),
             };
+            let line = Line(self.previous().line);
             let right = self.unary()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op: operator,
                 right: Box::new(right),
+                line,
             };
         }
 
         Ok(expr)
     }
 
-    fn unary(&mut self) -> Result<Expr, String> {
-        println!("got to unary");
+    fn unary(&mut self) -> Result<Expr, SpadeError> {
         if self.match_token(&[TokenType::Bang, TokenType::Minus]) {
             let operator = match self.previous().token_type {
                 TokenType::Bang => UnaryOp::Not,
                 TokenType::Minus => UnaryOp::Minus,
                 _ => unreachable!(),
             };
-            let right = self.unary()?;
+            let line = Line(self.previous().line);
+            let right = self.nested(|parser| parser.unary())?;
             return Ok(Expr::Unary {
                 op: operator,
                 expr: Box::new(right),
+                line,
             });
         }
 
-        self.call()
+        self.power()
+    }
+
+    // `**` binds tighter than unary operators on its left, so `-2 ** 2` is
+    // `-(2 ** 2)`, and is right-associative: `2 ** 3 ** 2` is `2 ** (3 ** 2)`.
+    // Its right operand may itself be unary, as in `2 ** -1`.
+    fn power(&mut self) -> Result<Expr, SpadeError> {
+        let base = self.call()?;
+        if !self.match_token(&[TokenType::StarStar]) {
+            return Ok(base);
+        }
+        let line = Line(self.previous().line);
+        let exponent = self.nested(|parser| parser.unary())?;
+        Ok(Expr::Binary { left: Box::new(base), op: BinaryOp::Power, right: Box::new(exponent), line })
+    }
+
+    // Text parts become string literals. Each embedded expression is parsed
+    // on its own and must use up all of its tokens.
+    fn interpolation(&mut self, parts: Box<[StringPart]>) -> Result<Expr, SpadeError> {
+        let string = self.previous().clone();
+        let mut exprs = vec![];
+        for part in parts {
+            match part {
+                StringPart::Text(text) => exprs.push(Expr::Literal(Literal::String(text))),
+                StringPart::Code(tokens) if tokens.is_empty() => {
+                    return Err(SpadeError::ParseError {
+                        message: "Expect expression inside '${}'".to_string(),
+                        line: string.line,
+                        column: string.column,
+                    });
+                },
+                StringPart::Code(tokens) => {
+                    let mut parser = Parser::new(tokens);
+                    parser.depth = self.depth;
+                    let expr = parser.nested(|parser| parser.expression())?;
+                    if !parser.is_at_end() {
+                        return Err(parser.error("Expect '}' after interpolated expression".to_string()));
+                    }
+                    exprs.push(expr);
+                },
+            }
+        }
+        Ok(Expr::Interpolation(exprs))
     }
 
-    fn primary(&mut self) -> Result<Expr, String> {
+    fn primary(&mut self) -> Result<Expr, SpadeError> {
         if self.match_token(&[TokenType::False]) {
             return Ok(Expr::Literal(Literal::Bool(false)));
         }
@@ -290,38 +664,80 @@ impl Parser {
         }
 
         if self.match_token(&[TokenType::Number]) {
-            println!("got to number match");
-            if let Some(crate::token::Literal::Number(value)) = &self.previous().literal {
-                return Ok(Expr::Literal(Literal::Number(*value)));
-            } else {
-                return Err("Number token without number literal".to_string());
-            }
+            return match &self.previous().literal {
+                Some(crate::token::Literal::Number(value)) => Ok(Expr::Literal(Literal::Number(*value))),
+                Some(crate::token::Literal::Integer(value)) => Ok(Expr::Literal(Literal::Integer(*value))),
+                _ => Err(self.error("Number token without number literal".to_string())),
+            };
         }
 
         if self.match_token(&[TokenType::Identifier]) {
-            return Ok(Expr::Literal(Literal::Var(self.previous().clone())));
+            return Ok(Expr::Literal(Literal::Var(self.previous().clone(), Resolution::Unresolved)));
         }
 
         if self.match_token(&[TokenType::String]) {
-            if let Some(crate::token::Literal::String(value)) = &self.previous().literal {
-                return Ok(Expr::Literal(Literal::String(value.clone())));
-            } else {
-                return Err("String token without string literal".to_string());
+            return match self.previous().literal.clone() {
+                Some(crate::token::Literal::String(value)) => Ok(Expr::Literal(Literal::String(value))),
+                Some(crate::token::Literal::Interpolation(parts)) => self.interpolation(parts),
+                _ => Err(self.error("String token without string literal".to_string())),
+            };
+        }
+
+        if self.match_token(&[TokenType::Fn]) {
+            return self.function_expression();
+        }
+
+        // The body of a let-expression extends as far right as possible.
+        if self.match_token(&[TokenType::Let]) {
+            let name = self.consume(&[TokenType::Identifier], "Expect name after 'let'".to_string())?;
+            self.consume(&[TokenType::Equal], "Expect '=' after let-expression name".to_string())?;
+            let value = self.expression()?;
+            self.consume(&[TokenType::In], "Expect 'in' after let-expression value".to_string())?;
+            let body = self.expression()?;
+            return Ok(Expr::Let { name: name.lexeme, value: Box::new(value), body: Box::new(body) });
+        }
+
+        if self.match_token(&[TokenType::LeftBracket]) {
+            let mut elements: Vec<Expr> = vec![];
+            while !self.is_at_end() && !self.check(TokenType::RightBracket) {
+                elements.push(self.expression()?);
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
             }
+            self.consume(&[TokenType::RightBracket], "Expect ']' after list elements".to_string())?;
+            return Ok(Expr::List(elements));
+        }
+
+        // In expression position `{` can only start a map literal; blocks are
+        // statements and never reach `primary`.
+        if self.match_token(&[TokenType::LeftBrace]) {
+            let mut entries: Vec<(Expr, Expr)> = vec![];
+            while !self.is_at_end() && !self.check(TokenType::RightBrace) {
+                let key = self.expression()?;
+                self.consume(&[TokenType::Colon], "Expect ':' after map key".to_string())?;
+                let value = self.expression()?;
+                entries.push((key, value));
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume(&[TokenType::RightBrace], "Expect '}' after map entries".to_string())?;
+            return Ok(Expr::Map(entries));
         }
 
         if self.match_token(&[TokenType::LeftParen]) {
             let expr = self.expression()?;
             if !self.match_token(&[TokenType::RightParen]) {
-                return Err("Expect ')' after expression".to_string());
+                return Err(self.error("Expect ')' after expression".to_string()));
             }
             return Ok(Expr::Grouping(Box::new(expr)));
         }
 
-        Err("Expect expression".to_string())
+        Err(self.error("Expect expression".to_string()))
     }
 
-    fn block(&mut self) -> Result<Vec<Statement>, String> {
+    fn block(&mut self) -> Result<Vec<Statement>, SpadeError> {
         let mut statements: Vec<Statement> = vec![];
         while !self.is_at_end() && !self.check(TokenType::RightBrace) {
             let stmt = self.statement()?;
@@ -331,19 +747,51 @@ impl Parser {
     }
 
 
-    pub fn parse_stmt(&mut self)  -> Result<Vec<Statement>, String> {
+    // Parses every top-level statement, recovering after each error so that
+    // all of them are reported rather than just the first.
+    pub fn parse_stmt(&mut self)  -> Result<Vec<Statement>, Vec<SpadeError>> {
         let mut statements: Vec<Statement> = vec![];
+        let mut errors: Vec<SpadeError> = vec![];
         while !self.is_at_end() {
-            let stmt = self.statement()?;
-            statements.push(stmt);
+            match self.statement() {
+                Ok(stmt) => statements.push(stmt),
+                Err(e) => {
+                    errors.push(e);
+                    self.synchronize();
+                },
+            }
         }
-        Ok(statements)
+        if errors.is_empty() { Ok(statements) } else { Err(errors) }
     }
 
-    fn end_arguments(&mut self) -> Result<Vec<Expr>, String> {
+    // Skips the rest of a broken statement: up to and including the next
+    // `;`, or up to the next keyword that starts a statement.
+    fn synchronize(&mut self) {
+        // Always move past the offending token so recovery makes progress.
+        self.advance();
+        while !self.is_at_end() {
+            if self.previous().token_type == TokenType::Semicolon {
+                return;
+            }
+            match self.peek().token_type {
+                TokenType::Print | TokenType::Let | TokenType::If | TokenType::Unless | TokenType::Fn
+                | TokenType::Return | TokenType::While | TokenType::For | TokenType::Import
+                | TokenType::Match | TokenType::With => return,
+                _ => {
+                    self.advance();
+                },
+            }
+        }
+    }
+
+    fn end_arguments(&mut self) -> Result<Vec<Expr>, SpadeError> {
         let mut arguments: Vec<Expr> = vec![];
         while !self.is_at_end() && !self.check(TokenType::RightParen) {
-            let expr = self.expression()?;
+            let expr = if self.match_token(&[TokenType::Ellipsis]) {
+                Expr::Spread(Box::new(self.expression()?))
+            } else {
+                self.expression()?
+            };
             arguments.push(expr);
             if !self.match_token(&[TokenType::Comma]) {
                 break;
@@ -353,25 +801,49 @@ impl Parser {
         Ok(arguments)
     }
 
-    fn call(&mut self) -> Result<Expr, String> {
+    fn call(&mut self) -> Result<Expr, SpadeError> {
         // for functions, the callee can either be an identifier,
         // or an expression that evaluates to a function.
         let mut expr = self.primary()?;
-        while self.match_token(&[TokenType::LeftParen]) {
-            let arguments = self.end_arguments()?;
-            expr = Expr::Call { callee: Box::new(expr), arguments };
+        loop {
+            if self.match_token(&[TokenType::LeftParen]) {
+                let line = Line(self.previous().line);
+                let arguments = self.end_arguments()?;
+                expr = Expr::Call { callee: Box::new(expr), arguments, line };
+            } else if self.match_token(&[TokenType::LeftBracket]) {
+                let line = Line(self.previous().line);
+                let index = self.expression()?;
+                self.consume(&[TokenType::RightBracket], "Expect ']' after index".to_string())?;
+                expr = Expr::Index { object: Box::new(expr), index: Box::new(index), line };
+            } else if self.match_token(&[TokenType::Dot]) {
+                let name = self.consume(&[TokenType::Identifier], "Expect property name after '.'".to_string())?;
+                expr = Expr::Get { object: Box::new(expr), name };
+            } else {
+                break;
+            }
         }
         Ok(expr)
     }
 }
 
-pub fn parse(tokens: Vec<Token>) -> Result<Expr, String> {
+/// Parses the tokens as a single expression, rejecting any left over.
+pub fn parse(tokens: Vec<Token>) -> Result<Expr, SpadeError> {
     let mut parser = Parser::new(tokens);
-    parser.expression()
+    let expr = parser.expression()?;
+    if !parser.is_at_end() {
+        return Err(parser.error("Expect end of expression".to_string()));
+    }
+    Ok(expr)
 }
 
 
-pub fn parse_stmt(tokens: Vec<Token>) -> Result<Vec<Statement>, String> {
+pub fn parse_stmt(tokens: Vec<Token>) -> Result<Vec<Statement>, SpadeError> {
+    parse_program(tokens).map_err(|errors| errors.into_iter().next().unwrap())
+}
+
+/// Parses a whole program, reporting every syntax error found instead of
+/// stopping at the first.
+pub fn parse_program(tokens: Vec<Token>) -> Result<Vec<Statement>, Vec<SpadeError>> {
     let mut parser = Parser::new(tokens);
     parser.parse_stmt()
 }
@@ -380,6 +852,7 @@ pub fn parse_stmt(tokens: Vec<Token>) -> Result<Vec<Statement>, String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::Rng;
     use crate::token::{scan_tokens};
 
     #[test]
@@ -396,7 +869,8 @@ mod tests {
         let ground_truth_expr = Expr::Binary {
             left: Box::new(Expr::Literal(Literal::Number(1.0))),
             op: BinaryOp::Plus,
-            right: Box::new(Expr::Literal(Literal::Number(2.0)))
+            right: Box::new(Expr::Literal(Literal::Number(2.0))),
+            line: Line(1),
         };
         assert_eq!(expr.to_string(), ground_truth_expr.to_string());
     }
@@ -407,7 +881,8 @@ mod tests {
         let expr = parse(tokens).unwrap();
         let ground_truth_expr = Expr::Unary {
             op: UnaryOp::Minus,
-            expr: Box::new(Expr::Literal(Literal::Number(42.0)))
+            expr: Box::new(Expr::Literal(Literal::Number(42.0))),
+            line: Line(1),
         };
         assert_eq!(expr.to_string(), ground_truth_expr.to_string());
     }
@@ -416,19 +891,328 @@ mod tests {
     fn test_variable_statement() {
         let tokens = scan_tokens("let dog = 3; print dog;".to_string()).unwrap();
         let declarations = parse_stmt(tokens).unwrap();
-        let ground_truth_declaration = vec![
-            Statement::VarDec { name: "dog".to_string(), initializer: Some(Expr::Literal(Literal::Number(3f64))) },
-            Statement::Print(
+        let ground_truth_declaration = [
+            Statement::VarDec { name: "dog".to_string(), initializer: Some(Expr::Literal(Literal::Number(3f64))), mutable: false },
+            Statement::Print(vec![
                     Expr::Literal(Literal::Var(Token {
                         token_type: crate::token::TokenType::Identifier,
                         lexeme: "dog".to_string(),
                         literal: Some(crate::token::Literal::Number(3f64)),
-                        line: 1
-                    }))
-                )
+                        line: 1,
+                        column: 1,
+                    }, Resolution::Unresolved))
+                ])
         ];
         assert_eq!(declarations[0].to_string(), ground_truth_declaration[0].to_string());
         assert_eq!(declarations[1].to_string(), ground_truth_declaration[1].to_string());
     }
-    
+
+    #[test]
+    fn test_if_display() {
+        let tokens = scan_tokens("if (a) { print 1; }".to_string()).unwrap();
+        let statements = parse_stmt(tokens).unwrap();
+        assert_eq!(statements[0].to_string(), "(if getvar a (block (print 1)))");
+
+        let tokens = scan_tokens("if (a) { print 1; } else { print 2; }".to_string()).unwrap();
+        let statements = parse_stmt(tokens).unwrap();
+        assert_eq!(statements[0].to_string(), "(if getvar a (block (print 1)) (else (block (print 2))))");
+    }
+
+    #[test]
+    fn test_else_if_chain_display() {
+        let source = "if (a) { print 1; } else if (b) { print 2; } else if (c) { print 3; } else { print 4; }";
+        let tokens = scan_tokens(source.to_string()).unwrap();
+        let statements = parse_stmt(tokens).unwrap();
+        assert_eq!(
+            statements[0].to_string(),
+            "(if getvar a (block (print 1)) (elif getvar b (block (print 2))) (elif getvar c (block (print 3))) (else (block (print 4))))"
+        );
+    }
+
+    #[test]
+    fn test_method_call() {
+        let tokens = scan_tokens("(3.7).floor()".to_string()).unwrap();
+        let expr = parse(tokens).unwrap();
+        assert_eq!(expr.to_string(), "(call (get (group 3.7) floor) )");
+
+        let tokens = scan_tokens("true.".to_string()).unwrap();
+        assert!(parse(tokens).is_err());
+    }
+
+    #[test]
+    fn test_expression_rejects_trailing_tokens() {
+        let tokens = scan_tokens("1 + 2 3".to_string()).unwrap();
+        match parse(tokens) {
+            Err(SpadeError::ParseError { message, column, .. }) => {
+                assert_eq!((message.as_str(), column), ("Expect end of expression", 7));
+            },
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_list_literal() {
+        let tokens = scan_tokens("[1, 2 + 3, [ ]]".to_string()).unwrap();
+        let expr = parse(tokens).unwrap();
+        assert_eq!(expr.to_string(), "(list 1 (2 + 3) (list ))");
+
+        let tokens = scan_tokens("[1, 2".to_string()).unwrap();
+        assert!(parse(tokens).is_err());
+    }
+
+    #[test]
+    fn test_mutable_declaration() {
+        let tokens = scan_tokens("let mut x = 1; let y = 2;".to_string()).unwrap();
+        let statements = parse_stmt(tokens).unwrap();
+        assert_eq!(statements[0].to_string(), "(var mut x 1)");
+        assert_eq!(statements[1].to_string(), "(var y 2)");
+    }
+
+    #[test]
+    fn test_declaration_without_initializer() {
+        let statements = parse_stmt(scan_tokens("let x; let mut y; let z = ;".to_string()).unwrap()).unwrap();
+        let displayed: Vec<String> = statements.iter().map(|s| s.to_string()).collect();
+        assert_eq!(displayed, ["(var x)", "(var mut y)", "(var z)"]);
+        assert!(parse_stmt(scan_tokens("let x 1;".to_string()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_spread_argument() {
+        let tokens = scan_tokens("f(1, ...xs)".to_string()).unwrap();
+        let expr = parse(tokens).unwrap();
+        assert_eq!(expr.to_string(), "(call getvar f 1, (spread getvar xs))");
+    }
+
+    #[test]
+    fn test_unless_desugars_to_if() {
+        let tokens = scan_tokens("unless (done) { print 1; } else { print 2; }".to_string()).unwrap();
+        let statements = parse_stmt(tokens).unwrap();
+        assert_eq!(statements[0].to_string(), "(if (!getvar done) (block (print 1)) (else (block (print 2))))");
+    }
+
+    const FRAGMENTS: &[&str] = &[
+        "(", ")", "{", "}", "[", "]", ",", ".", "...", "-", "+", "/", "*", ";", "!", "!=", "=",
+        "==", ">", ">=", "<", "<=", "\"", "\n", " ", "//", "1", "2.5", "3.", "x", "fn", "let",
+        "mut", "if", "else", "unless", "return", "print", "nil", "true", "é", "日本", "\u{0}", "#",
+    ];
+
+    #[test]
+    fn test_fuzz_source_never_panics() {
+        let mut rng = Rng(0x5eed_1234_abcd_ef01);
+        for _ in 0..5000 {
+            let source: String = if rng.below(4) == 0 {
+                let bytes: Vec<u8> = (0..rng.below(24)).map(|_| rng.next() as u8).collect();
+                String::from_utf8_lossy(&bytes).into_owned()
+            } else {
+                (0..rng.below(16)).map(|_| FRAGMENTS[rng.below(FRAGMENTS.len())]).collect()
+            };
+            if let Ok(tokens) = scan_tokens(source) {
+                let _ = parse(tokens.clone());
+                let _ = parse_stmt(tokens);
+            }
+        }
+    }
+
+    const TOKEN_TYPES: &[TokenType] = &[
+        TokenType::LeftParen, TokenType::RightParen, TokenType::LeftBrace, TokenType::RightBrace,
+        TokenType::LeftBracket, TokenType::RightBracket, TokenType::Comma, TokenType::Dot,
+        TokenType::DotDot, TokenType::Ellipsis, TokenType::Minus, TokenType::Plus, TokenType::Slash, TokenType::Star,
+        TokenType::Semicolon, TokenType::Bang, TokenType::BangEqual, TokenType::Equal,
+        TokenType::EqualEqual, TokenType::Greater, TokenType::Less, TokenType::Identifier,
+        TokenType::String, TokenType::Number, TokenType::Else, TokenType::Fn, TokenType::If,
+        TokenType::Nil, TokenType::Print, TokenType::Return, TokenType::Unless, TokenType::Let,
+        TokenType::Mut, TokenType::True, TokenType::EOF,
+    ];
+
+    #[test]
+    fn test_fuzz_tokens_never_panic() {
+        let mut rng = Rng(0x0dd_ba11_cafe_f00d);
+        for _ in 0..5000 {
+            let tokens: Vec<Token> = (0..rng.below(16)).map(|_| {
+                let token_type = TOKEN_TYPES[rng.below(TOKEN_TYPES.len())];
+                // Literal payloads are sometimes missing or mismatched on purpose.
+                let literal = match rng.below(3) {
+                    0 => None,
+                    1 => Some(crate::token::Literal::Number(1.0)),
+                    _ => Some(crate::token::Literal::String("s".to_string())),
+                };
+                Token { token_type, lexeme: "x".to_string(), literal, line: 1, column: 1 }
+            }).collect();
+            let _ = parse(tokens.clone());
+            let _ = parse_stmt(tokens);
+        }
+    }
+
+    #[test]
+    fn test_deep_nesting_is_an_error() {
+        for source in ["(".repeat(100_000) + "1", "{".repeat(100_000), "-".repeat(100_000) + "1"] {
+            let tokens = scan_tokens(source).unwrap();
+            assert!(parse(tokens.clone()).is_err());
+            assert!(parse_stmt(tokens).is_err());
+        }
+        let tokens = scan_tokens("(".repeat(60) + "1" + &")".repeat(60)).unwrap();
+        assert!(parse(tokens).is_ok());
+    }
+
+    #[test]
+    fn test_index_expression() {
+        let tokens = scan_tokens("xs[1][i + 1]".to_string()).unwrap();
+        let expr = parse(tokens).unwrap();
+        assert_eq!(expr.to_string(), "(index (index getvar xs 1) (getvar i + 1))");
+    }
+
+    #[test]
+    fn test_map_literal() {
+        let tokens = scan_tokens("let m = {\"a\": 1, \"b\": [2]}; { print m; }".to_string()).unwrap();
+        let statements = parse_stmt(tokens).unwrap();
+        assert_eq!(statements[0].to_string(), "(var m (map (\"a\" 1) (\"b\" (list 2))))");
+        assert_eq!(statements[1].to_string(), "(block (print getvar m))");
+    }
+
+    #[test]
+    fn test_with_statement() {
+        let tokens = scan_tokens("with (f = open()) { print f; }".to_string()).unwrap();
+        let statements = parse_stmt(tokens).unwrap();
+        assert_eq!(statements[0].to_string(), "(with f (call getvar open ) (block (print getvar f)))");
+        assert!(parse_stmt(scan_tokens("with (open()) {}".to_string()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_match_statement() {
+        let tokens = scan_tokens("match (x) { 1 if y => print 1; _ => {} }".to_string()).unwrap();
+        let statements = parse_stmt(tokens).unwrap();
+        assert_eq!(statements[0].to_string(), "(match getvar x (arm 1 (if getvar y) (print 1)) (arm _ (block )))");
+        assert!(parse_stmt(scan_tokens("match (x) { 1 print 1; }".to_string()).unwrap()).is_err());
+        assert!(parse_stmt(scan_tokens("match (x) { 1 => print 1;".to_string()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_import_statement() {
+        let tokens = scan_tokens("import \"lib/math.spade\";".to_string()).unwrap();
+        let statements = parse_stmt(tokens).unwrap();
+        assert_eq!(statements[0].to_string(), "(import \"lib/math.spade\")");
+        assert!(parse_stmt(scan_tokens("import math;".to_string()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_while_statement() {
+        let tokens = scan_tokens("while (i < 3) i = i + 1;".to_string()).unwrap();
+        let statements = parse_stmt(tokens).unwrap();
+        assert_eq!(statements[0].to_string(), "(while (getvar i < 3) (expr (assign i (getvar i + 1))))");
+        assert!(parse_stmt(scan_tokens("1 = 2;".to_string()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_conditional_assignment_desugars() {
+        let parse = |source: &str| parse_stmt(scan_tokens(source.to_string()).unwrap());
+        assert_eq!(parse("x ??= 1;").unwrap()[0].to_string(), "(expr (? (getvar x == nil) (assign x 1) getvar x))");
+        assert_eq!(parse("x ||= 1;").unwrap()[0].to_string(), "(expr (? getvar x getvar x (assign x 1)))");
+        assert!(parse("x.y ??= 1;").is_err());
+    }
+
+    #[test]
+    fn test_compound_assignment_desugars() {
+        let display = |source: &str| parse(scan_tokens(source.to_string()).unwrap()).unwrap().to_string();
+        assert_eq!(display("x += 1"), display("x = x + 1"));
+        assert_eq!(display("x *= 1 + 2"), "(assign x (getvar x * (1 + 2)))");
+        assert!(parse(scan_tokens("(x) -= 1".to_string()).unwrap()).is_err());
+        assert!(parse(scan_tokens("1 /= 2".to_string()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_for_statement_desugars() {
+        let parse = |source: &str| parse_stmt(scan_tokens(source.to_string()).unwrap());
+        assert_eq!(
+            parse("for (let i = 0; i < 3; i = i + 1) print i;").unwrap()[0].to_string(),
+            "(block (var mut i 0) (while (getvar i < 3) (print getvar i) (assign i (getvar i + 1))))",
+        );
+        assert_eq!(parse("for (;;) {}").unwrap()[0].to_string(), "(while true (block ))");
+        assert!(parse("for (let i = 0; i < 3) {}").is_err());
+    }
+
+    #[test]
+    fn test_print_statement() {
+        let parse = |source: &str| parse_stmt(scan_tokens(source.to_string()).unwrap());
+        assert_eq!(parse("print 1;").unwrap()[0].to_string(), "(print 1)");
+        assert_eq!(parse("print 1, f(2, 3), x;").unwrap()[0].to_string(), "(print 1 (call getvar f 2, 3) getvar x)");
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let parse_expr = |source: &str| parse(scan_tokens(source.to_string()).unwrap());
+        assert_eq!(parse_expr(r#""x=${1+1}""#).unwrap().to_string(), "(interpolate \"x=\" (1 + 1))");
+        assert_eq!(parse_expr(r#""${a}${b}""#).unwrap().to_string(), "(interpolate getvar a getvar b)");
+        assert!(parse_expr(r#""${}""#).is_err());
+        assert!(parse_expr(r#""${1 2}""#).is_err());
+    }
+
+    #[test]
+    fn test_for_in_statement() {
+        let parse = |source: &str| parse_stmt(scan_tokens(source.to_string()).unwrap());
+        assert_eq!(parse("for (x in xs) print x;").unwrap()[0].to_string(), "(for x getvar xs (print getvar x))");
+        assert_eq!(parse("for (x in 0..3) { break; }").unwrap()[0].to_string(), "(for x (0 .. 3) (block (break)))");
+        assert!(parse("for (x in xs print x;").is_err());
+    }
+
+    #[test]
+    fn test_logical_precedence() {
+        let expr = parse(scan_tokens("a or b and c == d".to_string()).unwrap()).unwrap();
+        assert_eq!(expr.to_string(), "(getvar a or (getvar b and (getvar c == getvar d)))");
+    }
+
+    #[test]
+    fn test_conditional_expression() {
+        let display = |source: &str| parse(scan_tokens(source.to_string()).unwrap()).unwrap().to_string();
+        assert_eq!(display("a or b ? 1 : 2"), "(? (getvar a or getvar b) 1 2)");
+        assert_eq!(display("a ? b : c ? d : e"), "(? getvar a getvar b (? getvar c getvar d getvar e))");
+        assert!(parse(scan_tokens("a ? b".to_string()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_anonymous_function() {
+        let parse = |source: &str| parse_stmt(scan_tokens(source.to_string()).unwrap());
+        let statements = parse("let add = fn(a, b) { return a + b; }; fn (x) {}(1);").unwrap();
+        assert_eq!(statements[0].to_string(), "(var add (lambda a, b (block (return (getvar a + getvar b)))))");
+        assert_eq!(statements[1].to_string(), "(expr (call (lambda x (block )) 1))");
+        // A loop around the function doesn't let its body `break`.
+        assert!(parse("while (true) { let f = fn() { break; }; }").is_err());
+    }
+
+    #[test]
+    fn test_power_precedence() {
+        let display = |source: &str| parse(scan_tokens(source.to_string()).unwrap()).unwrap().to_string();
+        assert_eq!(display("-2 ** 2"), "(-(2 ** 2))");
+        assert_eq!(display("2 ** 3 ** 2"), "(2 ** (3 ** 2))");
+        assert_eq!(display("2 ** -1"), "(2 ** (-1))");
+        assert_eq!(display("2 * 3 ** 2"), "(2 * (3 ** 2))");
+        assert_eq!(display("a.b(1) ** 2"), "((call (get getvar a b) 1) ** 2)");
+    }
+
+    #[test]
+    fn test_range_precedence() {
+        let display = |source: &str| parse(scan_tokens(source.to_string()).unwrap()).unwrap().to_string();
+        assert_eq!(display("0..n + 1"), "(0 .. (getvar n + 1))");
+        assert_eq!(display("0..3 == x"), "((0 .. 3) == getvar x)");
+        assert!(parse_stmt(scan_tokens("0..1..2;".to_string()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_break_and_continue_need_a_loop() {
+        let parse = |source: &str| parse_stmt(scan_tokens(source.to_string()).unwrap());
+        assert!(parse("while (true) { if (x) break; continue; }").is_ok());
+        assert_eq!(parse("break;").unwrap_err().to_string(), "'break' outside of a loop [line 1, column 1]");
+        assert_eq!(parse("x;\n  continue\n;").unwrap_err().to_string(), "'continue' outside of a loop [line 2, column 3]");
+        assert!(parse("while (true) { fn f() { continue; } }").is_err());
+    }
+
+    #[test]
+    fn test_reports_every_syntax_error() {
+        let source = "let = 1;\nprint 2;\nprint (3;\nprint 4;";
+        let errors = parse_program(scan_tokens(source.to_string()).unwrap()).unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, [
+            "'let' assignment must be provided a name [line 1, column 5]",
+            "Expect ')' after expression [line 3, column 9]",
+        ]);
+    }
 }